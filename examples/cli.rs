@@ -1,17 +1,20 @@
 use std::{
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
-    time::Duration,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
 use bitvec::view::BitView;
 use clap::*;
-use mdns_impl::packets::{pack::Packable, packet::MDNSPacket, response::MDNSResponse, MDNSTYPE};
+use mdns_impl::{
+    dns_sd::BrowseEvent,
+    packets::{pack::Packable, packet::MDNSPacket, response::MDNSResponse, MDNSTYPE},
+    querier::Querier,
+    socket::{mdns_socket, send, MDNS_MULTICAST_IPV4, MDNS_MULTICAST_IPV6},
+    transmit::MDNS_PORT,
+};
 
 // MDNS Constants
-const MDNS_PORT: u16 = 5353;
-const MDNS_MULTICAST_IPV4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
-const MDNS_MULTICAST_IPV6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x00fb);
 const MDNS_MULTICAST_SOCKETV4: SocketAddr =
     SocketAddr::new(IpAddr::V4(MDNS_MULTICAST_IPV4), MDNS_PORT);
 const MDNS_MULTICAST_SOCKETV6: SocketAddr =
@@ -56,16 +59,18 @@ fn mdns_query(source: (u32, IpAddr), service_type: &str, timeout: Duration) -> R
         socket.send_to(&buf[..num_bytes], target_address)?;
 
         let mut data = buf[..num_bytes].view_bits().to_bitvec();
-        let response = MDNSResponse::unpack(&mut data).expect("Failed to unpack response.");
+        let response = match MDNSResponse::unpack(&mut data) {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("Skipping unparseable packet: {e}");
+                continue;
+            }
+        };
 
-        println!(
-            "Found {}",
-            response
-                .get_resource_record_of_type(MDNSTYPE::SRV)
-                .unwrap()
-                .rr_name
-                .to_string()
-        );
+        let Ok(srv) = response.get_resource_record_of_type(MDNSTYPE::SRV) else {
+            continue;
+        };
+        println!("Found {}", srv.rr_name);
 
         responses.push(response);
     }
@@ -77,6 +82,61 @@ fn mdns_query(source: (u32, IpAddr), service_type: &str, timeout: Duration) -> R
     Ok(())
 }
 
+fn mdns_query_continuous(source: (u32, IpAddr), service_type: &str) -> Result<()> {
+    let socket = mdns_socket(source).expect("Failed to configure mDNS socket.");
+
+    let mut querier = Querier::new();
+    querier.set_interface(source.0);
+    let service_type = service_type
+        .trim_end_matches('.')
+        .trim_end_matches(".local");
+    let browser = querier.browse(&service_type.parse()?, Instant::now());
+
+    let mut buf = [0; 9000];
+    loop {
+        let now = Instant::now();
+        if querier.poll_timeout().is_some_and(|t| t <= now) {
+            querier.handle_timeout(now);
+        }
+
+        while let Some(transmit) = querier.poll_transmit() {
+            send(&socket, source.0, &transmit)?;
+        }
+
+        while querier.poll_event().is_some() {}
+        while let Some(event) = browser.try_recv() {
+            match event {
+                BrowseEvent::Added(instance) => println!("Found {}", instance.to_fqdn()),
+                BrowseEvent::Removed(instance) => println!("Lost {}", instance.to_fqdn()),
+            }
+        }
+
+        let timeout = querier
+            .poll_timeout()
+            .map(|t| t.saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::from_secs(1));
+        socket.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+
+        if let Ok((num_bytes, from)) = socket.recv_from(&mut buf) {
+            let mut data = buf[..num_bytes].view_bits().to_bitvec();
+            let response = match MDNSResponse::unpack(&mut data) {
+                Ok(response) => response,
+                // Anything on the shared port may turn up, including malformed packets.
+                Err(e) => {
+                    eprintln!("Skipping unparseable packet from {from}: {e}");
+                    continue;
+                }
+            };
+
+            if response.header.is_response() {
+                querier.handle_response(&response, Instant::now());
+            } else if from.ip() != source.1 {
+                querier.handle_query(&response, Instant::now());
+            }
+        }
+    }
+}
+
 // CLI Parser
 
 #[derive(Parser)]
@@ -87,6 +147,9 @@ struct MDNSCli {
     /// Max duration to wait for when recieving new .
     #[arg(short, long, default_value_t = DEFAULT_RESPONSE_READ_TIMEOUT)]
     timeout: f32,
+    /// Keep querying with exponential backoff, reporting services as they come and go.
+    #[arg(short, long)]
+    continuous: bool,
 }
 
 // ...
@@ -96,6 +159,10 @@ fn main() -> Result<()> {
 
     let adapter = get_or_select_ip_address()?;
 
+    if cli.continuous {
+        return mdns_query_continuous(adapter, &cli.service_type);
+    }
+
     mdns_query(
         adapter,
         &cli.service_type,
@@ -133,7 +200,7 @@ pub fn get_or_select_ip_address() -> Result<(u32, IpAddr)> {
         _ => {
             println!("Select an IP address to use by entering the corresponding index:");
             for (i, (ux, _, ip)) in usable_addresses.iter().enumerate() {
-                println!("{} [{}]: {}", i, interfaces[*ux as usize].name, ip);
+                println!("{} [{}]: {}", i, interfaces[*ux].name, ip);
             }
 
             let mut input = String::new();
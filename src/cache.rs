@@ -0,0 +1,267 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::packets::{
+    fqdn::MDNSFQDN, resource_record::MDNSResourceRecord, response::MDNSResponse, CLASS_ANY,
+    MDNSTYPE,
+};
+
+/// How long a flushed or goodbye record lingers before removal, see RFC 6762 section 10.1 and 10.2.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Records are cached per (name, type, class), with the name lowercased.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct CacheKey {
+    pub name: MDNSFQDN,
+    pub rr_type: MDNSTYPE,
+    pub class: u16,
+}
+
+impl CacheKey {
+    pub fn new(name: &MDNSFQDN, rr_type: MDNSTYPE, class: u16) -> Self {
+        CacheKey {
+            name: name.to_ascii_lowercase(),
+            rr_type,
+            class,
+        }
+    }
+
+    pub fn of(record: &MDNSResourceRecord) -> Self {
+        CacheKey::new(&record.rr_name, record.rr_type, record.class())
+    }
+
+    /// Whether a lookup for this key should return records stored under `other`, honouring `ANY`.
+    fn matches(&self, other: &CacheKey) -> bool {
+        self.name == other.name
+            && (self.rr_type == MDNSTYPE::ANY || self.rr_type == other.rr_type)
+            && (self.class == CLASS_ANY || self.class == other.class)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CachedRecord {
+    pub record: MDNSResourceRecord,
+    /// When the record was last received.
+    pub received: Instant,
+    /// When the record will be removed, normally `received + ttl`.
+    pub expires: Instant,
+}
+
+impl CachedRecord {
+    pub fn remaining_ttl(&self, now: Instant) -> Duration {
+        self.expires.saturating_duration_since(now)
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires <= now
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CacheEvent {
+    Added(MDNSResourceRecord),
+    Removed(MDNSResourceRecord),
+}
+
+/// A TTL-aware cache of received resource records.
+///
+/// Every method takes the current time, and changes are queued as [`CacheEvent`]s to be drained
+/// with [`RecordCache::poll_event`].
+#[derive(Debug, Default)]
+pub struct RecordCache {
+    records: HashMap<CacheKey, Vec<CachedRecord>>,
+    events: VecDeque<CacheEvent>,
+}
+
+impl RecordCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Caches the answer and additional sections of a response.
+    pub fn insert_response(&mut self, response: &MDNSResponse, now: Instant) {
+        response
+            .answers
+            .iter()
+            .chain(response.additional.iter())
+            .for_each(|record| self.insert(record.clone(), now));
+    }
+
+    pub fn insert(&mut self, record: MDNSResourceRecord, now: Instant) {
+        let key = CacheKey::of(&record);
+        // A goodbye for a name we hold nothing for has nothing to remove.
+        if record.ttl == 0 && !self.records.contains_key(&key) {
+            return;
+        }
+        let entries = self.records.entry(key).or_default();
+
+        /* https://www.rfc-editor.org/rfc/rfc6762.html#section-10.2
+            ... rather than flushing all records with that name, rrtype, and rrclass
+            immediately, they are instead set to expire one second in the future ...
+            records received within the last second are not flushed, as they are
+            presumed to be part of the same burst of packets.
+        */
+        if record.cache_flush() {
+            entries
+                .iter_mut()
+                .filter(|e| e.record.rdata() != record.rdata() && e.received + GRACE_PERIOD < now)
+                .for_each(|e| e.expires = e.expires.min(now + GRACE_PERIOD));
+        }
+
+        let existing = entries
+            .iter_mut()
+            .find(|e| e.record.rdata() == record.rdata());
+
+        match existing {
+            // A goodbye only schedules removal of a record we already hold.
+            Some(e) if record.ttl == 0 => e.expires = e.expires.min(now + GRACE_PERIOD),
+            None if record.ttl == 0 => {}
+            Some(e) => {
+                e.expires = now + Duration::from_secs(record.ttl as u64);
+                e.received = now;
+                e.record = record;
+            }
+            None => {
+                entries.push(CachedRecord {
+                    expires: now + Duration::from_secs(record.ttl as u64),
+                    received: now,
+                    record: record.clone(),
+                });
+                self.events.push_back(CacheEvent::Added(record));
+            }
+        }
+    }
+
+    /// Returns the unexpired records matching the given name, type and class, either of which may
+    /// be `ANY`.
+    pub fn lookup(
+        &self,
+        name: &MDNSFQDN,
+        rr_type: MDNSTYPE,
+        class: u16,
+        now: Instant,
+    ) -> Vec<&CachedRecord> {
+        let key = CacheKey::new(name, rr_type, class);
+
+        self.records
+            .iter()
+            .filter(|(k, _)| key.matches(k))
+            .flat_map(|(_, entries)| entries.iter())
+            .filter(|e| !e.is_expired(now))
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CachedRecord> {
+        self.records.values().flatten()
+    }
+
+    /// Removes every record whose TTL or grace period has run out.
+    pub fn expire(&mut self, now: Instant) {
+        let events = &mut self.events;
+
+        self.records.retain(|_, entries| {
+            entries.retain(|e| {
+                let expired = e.is_expired(now);
+                if expired {
+                    events.push_back(CacheEvent::Removed(e.record.clone()));
+                }
+                !expired
+            });
+            !entries.is_empty()
+        });
+    }
+
+    /// The next point in time at which [`RecordCache::expire`] will remove something.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.iter().map(|e| e.expires).min()
+    }
+
+    pub fn poll_event(&mut self) -> Option<CacheEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ptr(instance: &str, ttl: u32, cache_flush: bool) -> MDNSResourceRecord {
+        MDNSResourceRecord::new(
+            MDNSFQDN::new("_http._tcp.local"),
+            MDNSTYPE::PTR,
+            cache_flush,
+            ttl,
+            instance.as_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_expiry_and_goodbye() {
+        let now = Instant::now();
+        let mut cache = RecordCache::new();
+
+        cache.insert(ptr("a", 120, false), now);
+        cache.insert(ptr("b", 10, false), now);
+        assert_eq!(
+            cache.poll_event(),
+            Some(CacheEvent::Added(ptr("a", 120, false)))
+        );
+        assert_eq!(
+            cache.poll_event(),
+            Some(CacheEvent::Added(ptr("b", 10, false)))
+        );
+
+        let name = MDNSFQDN::new("_HTTP._tcp.local");
+        assert_eq!(cache.lookup(&name, MDNSTYPE::ANY, CLASS_ANY, now).len(), 2);
+
+        // A goodbye keeps the record around for one more second.
+        cache.insert(ptr("a", 0, false), now);
+        cache.expire(now + Duration::from_millis(500));
+        assert_eq!(cache.len(), 2);
+
+        cache.expire(now + Duration::from_secs(10));
+        assert_eq!(
+            cache.poll_event(),
+            Some(CacheEvent::Removed(ptr("a", 120, false)))
+        );
+        assert_eq!(
+            cache.poll_event(),
+            Some(CacheEvent::Removed(ptr("b", 10, false)))
+        );
+        assert!(cache.is_empty());
+
+        // A goodbye for a record that was never cached leaves nothing behind.
+        cache.insert(ptr("c", 0, false), now + Duration::from_secs(10));
+        assert!(cache.is_empty());
+        assert_eq!(cache.poll_event(), None);
+    }
+
+    #[test]
+    fn test_cache_flush_grace() {
+        let now = Instant::now();
+        let mut cache = RecordCache::new();
+
+        cache.insert(ptr("old", 120, true), now);
+        // Records within the same second are part of the same burst and survive.
+        cache.insert(ptr("burst", 120, true), now + Duration::from_millis(200));
+        cache.expire(now + Duration::from_secs(5));
+        assert_eq!(cache.len(), 2);
+
+        let later = now + Duration::from_secs(5);
+        cache.insert(ptr("new", 120, true), later);
+        cache.expire(later + Duration::from_millis(999));
+        assert_eq!(cache.len(), 3);
+        cache.expire(later + GRACE_PERIOD);
+        assert_eq!(cache.len(), 1);
+    }
+}
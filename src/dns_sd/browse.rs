@@ -0,0 +1,155 @@
+use std::sync::{
+    mpsc::{channel, Receiver},
+    Arc, Weak,
+};
+
+use super::{ServiceInstanceName, ServiceType};
+use crate::{
+    cache::CacheEvent,
+    packets::{fqdn::MDNSFQDN, MDNSTYPE},
+    querier::QueryHandle,
+};
+
+/// A name a [`Browser`] reports, parsed from the rdata of the PTR records browsed for.
+pub trait BrowseName: Sized {
+    /// Returns `None` for names that don't parse, which are then never reported.
+    fn from_ptr(target: &MDNSFQDN) -> Option<Self>;
+}
+
+impl BrowseName for ServiceInstanceName {
+    fn from_ptr(target: &MDNSFQDN) -> Option<Self> {
+        ServiceInstanceName::try_from(target).ok()
+    }
+}
+
+impl BrowseName for ServiceType {
+    fn from_ptr(target: &MDNSFQDN) -> Option<Self> {
+        ServiceType::from_fqdn(target).ok().map(|(t, _)| t)
+    }
+}
+
+/// Domains, as reported by [`crate::querier::Querier::browse_domains`].
+impl BrowseName for MDNSFQDN {
+    fn from_ptr(target: &MDNSFQDN) -> Option<Self> {
+        Some(target.clone())
+    }
+}
+
+/// A name coming or going, by default a service instance, or otherwise the service type or
+/// domain named by a [`BrowseName`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BrowseEvent<T = ServiceInstanceName> {
+    Added(T),
+    Removed(T),
+}
+
+/// Browses for instances of a service type until dropped, see [`crate::querier::Querier::browse`].
+///
+/// Browsing for service types or domains instead reports those, as `T`.
+///
+/// Events arrive as the querier handles responses and timeouts, so on the thread driving it they
+/// should be drained with [`Browser::try_recv`].
+#[derive(Debug)]
+pub struct Browser<T = ServiceInstanceName> {
+    _handle: QueryHandle,
+    _token: Arc<()>,
+    events: Receiver<BrowseEvent<T>>,
+}
+
+impl<T> Browser<T> {
+    pub(crate) fn new(
+        handle: QueryHandle,
+        events: Receiver<BrowseEvent<T>>,
+        token: Arc<()>,
+    ) -> Self {
+        Browser {
+            _handle: handle,
+            _token: token,
+            events,
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<BrowseEvent<T>> {
+        self.events.try_recv().ok()
+    }
+
+    pub fn events(&self) -> &Receiver<BrowseEvent<T>> {
+        &self.events
+    }
+}
+
+/// The querier's side of a [`Browser`], turning cache events into instances coming and going.
+pub(crate) struct Browse {
+    service: MDNSFQDN,
+    /// Lowercased instances with how many cached records name them, as the same instance may be
+    /// cached in several cases.
+    instances: Vec<(MDNSFQDN, usize)>,
+    /// Parses and reports a change, returning `false` once the [`Browser`] is dropped.
+    report: Box<dyn FnMut(BrowseEvent<MDNSFQDN>) -> bool + Send>,
+    /// Dropped along with the [`Browser`].
+    owner: Weak<()>,
+}
+
+impl Browse {
+    pub(crate) fn new<T: BrowseName + Send + 'static>(
+        service: MDNSFQDN,
+    ) -> (Self, Receiver<BrowseEvent<T>>, Arc<()>) {
+        let (sender, events) = channel();
+        let token = Arc::new(());
+        let report = move |event| {
+            let event = match event {
+                BrowseEvent::Added(name) => T::from_ptr(&name).map(BrowseEvent::Added),
+                BrowseEvent::Removed(name) => T::from_ptr(&name).map(BrowseEvent::Removed),
+            };
+            event.is_none_or(|event| sender.send(event).is_ok())
+        };
+        let browse = Browse {
+            service,
+            instances: vec![],
+            report: Box::new(report),
+            owner: Arc::downgrade(&token),
+        };
+
+        (browse, events, token)
+    }
+
+    /// Whether the [`Browser`] is still around to report to.
+    pub(crate) fn is_alive(&self) -> bool {
+        self.owner.strong_count() > 0
+    }
+
+    /// Reports any change to the instances, returning `false` once the [`Browser`] is dropped.
+    pub(crate) fn observe(&mut self, event: &CacheEvent) -> bool {
+        let (CacheEvent::Added(record) | CacheEvent::Removed(record)) = event;
+        if record.rr_type != MDNSTYPE::PTR || !record.rr_name.eq_ignore_ascii_case(&self.service) {
+            return true;
+        }
+        let Ok(instance) = record.rdata_as::<MDNSFQDN>() else {
+            return true;
+        };
+
+        let key = instance.to_ascii_lowercase();
+        let known = self.instances.iter().position(|(i, _)| *i == key);
+        let event = match (event, known) {
+            (CacheEvent::Added(_), None) => {
+                self.instances.push((key, 1));
+                BrowseEvent::Added(instance)
+            }
+            (CacheEvent::Added(_), Some(index)) => {
+                self.instances[index].1 += 1;
+                return true;
+            }
+            (CacheEvent::Removed(_), Some(index)) if self.instances[index].1 > 1 => {
+                self.instances[index].1 -= 1;
+                return true;
+            }
+            (CacheEvent::Removed(_), Some(index)) => {
+                self.instances.remove(index);
+                BrowseEvent::Removed(instance)
+            }
+            (CacheEvent::Removed(_), None) => return true,
+        };
+
+        (self.report)(event)
+    }
+}
@@ -0,0 +1,51 @@
+use super::OTHER_TTL;
+use crate::{
+    packets::{fqdn::MDNSFQDN, pack::Packable, resource_record::MDNSResourceRecord, MDNSTYPE},
+    responder::AuthoritativeRecord,
+};
+
+/// The kinds of domain a network can recommend, each found under its own
+/// `<kind>._dns-sd._udp.<domain>` name, see RFC 6763 section 11.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DomainKind {
+    /// `b`, domains recommended for browsing.
+    Browse,
+    /// `db`, the single recommended default domain for browsing.
+    DefaultBrowse,
+    /// `r`, domains recommended for registering services.
+    Register,
+    /// `dr`, the single recommended default domain for registering services.
+    DefaultRegister,
+    /// `lb`, domains to browse automatically, such as by legacy clients.
+    LegacyBrowse,
+}
+
+impl DomainKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DomainKind::Browse => "b",
+            DomainKind::DefaultBrowse => "db",
+            DomainKind::Register => "r",
+            DomainKind::DefaultRegister => "dr",
+            DomainKind::LegacyBrowse => "lb",
+        }
+    }
+
+    /// `<kind>._dns-sd._udp.<domain>`, the name whose PTR records list the recommended domains.
+    pub fn to_fqdn(&self, domain: &str) -> MDNSFQDN {
+        MDNSFQDN::new(&format!("{}._dns-sd._udp.{domain}", self.label()))
+    }
+
+    /// The shared PTR record recommending `recommended` in the `local` domain.
+    pub fn record(&self, recommended: &MDNSFQDN) -> AuthoritativeRecord {
+        let rdata = recommended.pack().into_vec();
+        let record = MDNSResourceRecord::new(
+            self.to_fqdn("local"),
+            MDNSTYPE::PTR,
+            false,
+            OTHER_TTL,
+            rdata,
+        );
+        AuthoritativeRecord::shared(record)
+    }
+}
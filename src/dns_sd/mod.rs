@@ -0,0 +1,24 @@
+//! DNS-Based Service Discovery (RFC 6763) on top of the [`crate::querier`] and
+//! [`crate::responder`].
+
+pub use self::browse::{BrowseEvent, BrowseName, Browser};
+pub use self::domain::DomainKind;
+pub use self::name::{Protocol, ServiceInstanceName, ServiceType};
+pub use self::resolve::{ResolveEvent, ResolvedService, Resolver};
+pub use self::service::ServiceInfo;
+pub use self::subtype::Subtype;
+
+pub(crate) use self::browse::Browse;
+pub(crate) use self::resolve::Resolve;
+
+mod browse;
+mod domain;
+mod name;
+mod resolve;
+mod service;
+mod subtype;
+
+/// The TTL of records naming a host, see RFC 6762 section 10.
+pub const HOST_TTL: u32 = 120;
+/// The TTL of every other record.
+pub const OTHER_TTL: u32 = 4500;
@@ -0,0 +1,222 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+use crate::packets::fqdn::{Label, MDNSFQDN};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Protocol {
+    Tcp,
+    /// Used for every protocol other than TCP, see RFC 6763 section 7.
+    Udp,
+}
+
+impl Protocol {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "_tcp",
+            Protocol::Udp => "_udp",
+        }
+    }
+}
+
+/// A service type such as `_http._tcp`, see RFC 6763 section 7.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ServiceType {
+    /// The service name without its leading underscore, such as `http`.
+    name: String,
+    protocol: Protocol,
+}
+
+impl ServiceType {
+    /* https://www.rfc-editor.org/rfc/rfc6335.html#section-5.1
+        Valid service names ... MUST be at least 1 character and no more than 15
+        characters long, MUST contain only US-ASCII letters 'A' - 'Z' and 'a' - 'z',
+        digits '0' - '9', and hyphens ('-', ASCII 0x2D or decimal 45), MUST contain at
+        least one letter ('A' - 'Z' or 'a' - 'z'), MUST NOT begin or end with a hyphen,
+        and hyphens MUST NOT be adjacent to other hyphens.
+    */
+    pub fn new(name: &str, protocol: Protocol) -> Result<Self> {
+        let valid = (1..=15).contains(&name.len())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && name.chars().any(|c| c.is_ascii_alphabetic())
+            && !name.starts_with('-')
+            && !name.ends_with('-')
+            && !name.contains("--");
+        if !valid {
+            bail!("'{name}' is not a valid service name");
+        }
+
+        Ok(ServiceType {
+            name: name.to_string(),
+            protocol,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// `_<name>._<protocol>.<domain>`, such as `_http._tcp.local`.
+    pub fn to_fqdn(&self, domain: &str) -> MDNSFQDN {
+        MDNSFQDN::new(&format!("{self}.{domain}"))
+    }
+
+    /// Splits a name such as `_http._tcp.local` into the service type and its domain.
+    pub fn from_fqdn(name: &MDNSFQDN) -> Result<(Self, String)> {
+        let [Label::String(service), Label::String(protocol), domain @ ..] = &name.labels[..]
+        else {
+            bail!("'{name}' is not a service type name");
+        };
+        if domain.is_empty() {
+            bail!("'{name}' has no domain");
+        }
+
+        let service_type = format!("{service}.{protocol}").parse()?;
+        let domain = MDNSFQDN {
+            labels: domain.to_vec(),
+        };
+        Ok((service_type, domain.to_string()))
+    }
+}
+
+impl FromStr for ServiceType {
+    type Err = anyhow::Error;
+
+    /// Parses `_<name>._tcp` or `_<name>._udp`.
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((name, protocol)) = s.split_once('.') else {
+            bail!("'{s}' is not of the form _<service>._tcp or _<service>._udp");
+        };
+        let Some(name) = name.strip_prefix('_') else {
+            bail!("'{s}' must start with an underscore");
+        };
+        let protocol = match protocol.to_ascii_lowercase().as_str() {
+            "_tcp" => Protocol::Tcp,
+            "_udp" => Protocol::Udp,
+            _ => bail!("'{s}' must end with _tcp or _udp"),
+        };
+
+        ServiceType::new(name, protocol)
+    }
+}
+
+impl std::fmt::Display for ServiceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "_{}.{}", self.name, self.protocol.label())
+    }
+}
+
+/// A service instance name, keeping the user-visible instance label apart from the type and
+/// domain, see RFC 6763 section 4.1.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ServiceInstanceName {
+    /// Any UTF-8 up to 63 bytes, dots included.
+    instance: String,
+    service_type: ServiceType,
+    domain: String,
+}
+
+impl ServiceInstanceName {
+    pub fn new(instance: &str, service_type: ServiceType, domain: &str) -> Result<Self> {
+        if instance.is_empty() || instance.len() > 63 {
+            bail!("Instance name '{instance}' must be 1 to 63 bytes long");
+        }
+
+        Ok(ServiceInstanceName {
+            instance: instance.to_string(),
+            service_type,
+            domain: domain.to_string(),
+        })
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    pub fn service_type(&self) -> &ServiceType {
+        &self.service_type
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The full name, with the instance as a single label even if it contains dots.
+    pub fn to_fqdn(&self) -> MDNSFQDN {
+        let mut labels = vec![Label::String(self.instance.clone())];
+        labels.extend(self.service_type.to_fqdn(&self.domain).labels);
+        MDNSFQDN { labels }
+    }
+}
+
+impl TryFrom<&MDNSFQDN> for ServiceInstanceName {
+    type Error = anyhow::Error;
+
+    fn try_from(name: &MDNSFQDN) -> Result<Self> {
+        let [Label::String(instance), rest @ ..] = &name.labels[..] else {
+            bail!("'{name}' is not a service instance name");
+        };
+        let (service_type, domain) = ServiceType::from_fqdn(&MDNSFQDN {
+            labels: rest.to_vec(),
+        })?;
+
+        ServiceInstanceName::new(instance, service_type, &domain)
+    }
+}
+
+impl From<&ServiceInstanceName> for MDNSFQDN {
+    fn from(name: &ServiceInstanceName) -> Self {
+        name.to_fqdn()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_type() {
+        let http = "_http._tcp".parse::<ServiceType>().unwrap();
+        assert_eq!(http.name(), "http");
+        assert_eq!(http.protocol(), Protocol::Tcp);
+        assert_eq!(http.to_fqdn("local"), MDNSFQDN::new("_http._tcp.local"));
+        assert_eq!(
+            ServiceType::from_fqdn(&MDNSFQDN::new("_http._tcp.local")).unwrap(),
+            (http, "local".to_string())
+        );
+
+        for invalid in [
+            "http._tcp",
+            "_http._sctp",
+            "_http",
+            "_._tcp",
+            "_abcdefghijklmnop._tcp",
+            "_-http._tcp",
+            "_ht--tp._tcp",
+            "_123._udp",
+            "_ht_tp._udp",
+        ] {
+            assert!(invalid.parse::<ServiceType>().is_err(), "{invalid}");
+        }
+        assert!("_abcdefghijklmno._udp".parse::<ServiceType>().is_ok());
+    }
+
+    #[test]
+    fn test_service_instance_name() {
+        let http = "_http._tcp".parse::<ServiceType>().unwrap();
+        let name = ServiceInstanceName::new("Mr. Printer", http.clone(), "local").unwrap();
+
+        let fqdn = name.to_fqdn();
+        assert_eq!(fqdn.labels.len(), 4);
+        assert_eq!(fqdn.to_string(), "Mr. Printer._http._tcp.local");
+        assert_eq!(ServiceInstanceName::try_from(&fqdn).unwrap(), name);
+
+        assert!(ServiceInstanceName::try_from(&MDNSFQDN::new("_http._tcp.local")).is_err());
+        assert!(ServiceInstanceName::new(&"x".repeat(64), http, "local").is_err());
+    }
+}
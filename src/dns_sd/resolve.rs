@@ -0,0 +1,162 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Weak,
+    },
+    time::Instant,
+};
+
+use super::ServiceInstanceName;
+use crate::{
+    cache::RecordCache,
+    host::address,
+    packets::{
+        fqdn::MDNSFQDN,
+        rdata::{SRV, TXT},
+        CLASS_ANY, MDNSTYPE,
+    },
+    querier::QueryHandle,
+};
+
+/// Everything needed to connect to a service instance.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ResolvedService {
+    pub instance: ServiceInstanceName,
+    pub host: MDNSFQDN,
+    pub port: u16,
+    /// Every known address of `host`, IPv4 first.
+    pub addresses: Vec<IpAddr>,
+    pub txt: TXT,
+    /// The interface the querier runs on, if it was told, see
+    /// [`crate::querier::Querier::set_interface`].
+    pub interface: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ResolveEvent {
+    /// The service was resolved, or some part of it changed.
+    Resolved(ResolvedService),
+    /// The service's SRV record went away, by goodbye or expiry.
+    Removed,
+}
+
+/// Resolves a service instance until dropped, see [`crate::querier::Querier::resolve`].
+///
+/// A new [`ResolvedService`] is sent whenever any part of it changes, so the latest one received
+/// is current.
+#[derive(Debug)]
+pub struct Resolver {
+    _handles: [QueryHandle; 2],
+    _token: Arc<()>,
+    updates: Receiver<ResolveEvent>,
+}
+
+impl Resolver {
+    pub(crate) fn new(
+        handles: [QueryHandle; 2],
+        updates: Receiver<ResolveEvent>,
+        token: Arc<()>,
+    ) -> Self {
+        Resolver {
+            _handles: handles,
+            _token: token,
+            updates,
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<ResolveEvent> {
+        self.updates.try_recv().ok()
+    }
+
+    pub fn updates(&self) -> &Receiver<ResolveEvent> {
+        &self.updates
+    }
+}
+
+/// The querier's side of a [`Resolver`].
+#[derive(Debug)]
+pub(crate) struct Resolve {
+    pub(crate) instance: ServiceInstanceName,
+    /// The SRV target whose addresses are being asked for, and the questions doing so.
+    pub(crate) target: Option<(MDNSFQDN, Vec<QueryHandle>)>,
+    last: Option<ResolvedService>,
+    sender: Sender<ResolveEvent>,
+    /// Dropped along with the [`Resolver`].
+    owner: Weak<()>,
+}
+
+impl Resolve {
+    pub(crate) fn new(instance: ServiceInstanceName) -> (Self, Receiver<ResolveEvent>, Arc<()>) {
+        let (sender, updates) = channel();
+        let token = Arc::new(());
+        let resolve = Resolve {
+            instance,
+            target: None,
+            last: None,
+            sender,
+            owner: Arc::downgrade(&token),
+        };
+
+        (resolve, updates, token)
+    }
+
+    /// The service as currently cached, once its SRV record is known.
+    pub(crate) fn current(
+        &self,
+        cache: &RecordCache,
+        interface: Option<u32>,
+        now: Instant,
+    ) -> Option<ResolvedService> {
+        let name = self.instance.to_fqdn();
+        // After a cache flush the replaced record lingers for a second, so prefer the newest.
+        let srv = cache
+            .lookup(&name, MDNSTYPE::SRV, CLASS_ANY, now)
+            .into_iter()
+            .max_by_key(|e| e.received)?
+            .record
+            .rdata_as::<SRV>()
+            .ok()?;
+        let txt = cache
+            .lookup(&name, MDNSTYPE::TXT, CLASS_ANY, now)
+            .into_iter()
+            .max_by_key(|e| e.received)
+            .and_then(|e| e.record.rdata_as::<TXT>().ok())
+            .unwrap_or_default();
+
+        let mut addresses = cache
+            .lookup(&srv.target, MDNSTYPE::ANY, CLASS_ANY, now)
+            .into_iter()
+            .filter_map(|e| address(e.record.rr_type, e.record.rdata()))
+            .collect::<Vec<_>>();
+        addresses.sort();
+        addresses.dedup();
+
+        Some(ResolvedService {
+            instance: self.instance.clone(),
+            host: srv.target,
+            port: srv.port,
+            addresses,
+            txt,
+            interface,
+        })
+    }
+
+    /// Reports `current` if it differs from what was reported last, returning `false` once the
+    /// [`Resolver`] is dropped.
+    pub(crate) fn update(&mut self, current: Option<ResolvedService>) -> bool {
+        if self.owner.strong_count() == 0 {
+            return false;
+        }
+        if current == self.last {
+            return true;
+        }
+
+        self.last = current.clone();
+        let event = match current {
+            Some(current) => ResolveEvent::Resolved(current),
+            None => ResolveEvent::Removed,
+        };
+        self.sender.send(event).is_ok()
+    }
+}
@@ -0,0 +1,102 @@
+use std::net::IpAddr;
+
+use super::{ServiceInstanceName, Subtype, HOST_TTL, OTHER_TTL};
+use crate::{
+    packets::{
+        fqdn::MDNSFQDN,
+        pack::Packable,
+        rdata::{SRV, TXT},
+        resource_record::MDNSResourceRecord,
+        MDNSTYPE,
+    },
+    responder::AuthoritativeRecord,
+};
+
+/// Everything needed to advertise a service instance, see RFC 6763 section 4.1.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ServiceInfo {
+    /// Such as `Mr. Printer` of type `_ipp._tcp` in `local`.
+    pub name: ServiceInstanceName,
+    pub port: u16,
+    /// The host the service runs on, such as `printer.local`.
+    pub host: MDNSFQDN,
+    pub txt: TXT,
+    /// Subtypes the instance is also registered under, such as `_printer`.
+    pub subtypes: Vec<Subtype>,
+    /// Addresses of `host` to publish alongside the service, if nothing else publishes them.
+    pub addresses: Vec<IpAddr>,
+}
+
+impl ServiceInfo {
+    /// `<service_type>.<domain>`, the name browsed for instances.
+    pub fn service_name(&self) -> MDNSFQDN {
+        self.name.service_type().to_fqdn(self.name.domain())
+    }
+
+    /// `<instance>.<service_type>.<domain>`, keeping the instance a single label.
+    pub fn instance_name(&self) -> MDNSFQDN {
+        self.name.to_fqdn()
+    }
+
+    /// Every record advertising the service.
+    ///
+    /// The PTR records naming the instance are shared, while the SRV, TXT and address records
+    /// are unique and so probed for.
+    pub fn records(&self) -> Vec<AuthoritativeRecord> {
+        let instance = self.instance_name();
+        let ptr = |name: MDNSFQDN, target: &MDNSFQDN| {
+            let rdata = target.pack().into_vec();
+            let record = MDNSResourceRecord::new(name, MDNSTYPE::PTR, false, OTHER_TTL, rdata);
+            AuthoritativeRecord::shared(record)
+        };
+
+        let srv = SRV {
+            priority: 0,
+            weight: 0,
+            port: self.port,
+            target: self.host.clone(),
+        };
+
+        let mut records = vec![
+            ptr(self.service_name(), &instance),
+            AuthoritativeRecord::unique(MDNSResourceRecord::new(
+                instance.clone(),
+                MDNSTYPE::SRV,
+                false,
+                HOST_TTL,
+                srv.pack().into_vec(),
+            )),
+            AuthoritativeRecord::unique(MDNSResourceRecord::new(
+                instance.clone(),
+                MDNSTYPE::TXT,
+                false,
+                OTHER_TTL,
+                self.txt.pack().into_vec(),
+            )),
+        ];
+
+        for subtype in &self.subtypes {
+            records.push(ptr(subtype.to_fqdn(&self.service_name()), &instance));
+        }
+
+        /* https://www.rfc-editor.org/rfc/rfc6763.html#section-9
+            ... a DNS query for PTR records with the name "_services._dns-sd._udp.<Domain>"
+            yields a set of PTR records, where the rdata of each PTR record is the two-label
+            <Service> name, plus the same domain ...
+        */
+        let meta = MDNSFQDN::new(&format!("_services._dns-sd._udp.{}", self.name.domain()));
+        records.push(ptr(meta, &self.service_name()));
+
+        for address in &self.addresses {
+            let (rr_type, rdata) = match address {
+                IpAddr::V4(v4) => (MDNSTYPE::A, v4.octets().to_vec()),
+                IpAddr::V6(v6) => (MDNSTYPE::AAAA, v6.octets().to_vec()),
+            };
+            let record =
+                MDNSResourceRecord::new(self.host.clone(), rr_type, false, HOST_TTL, rdata);
+            records.push(AuthoritativeRecord::unique(record));
+        }
+
+        records
+    }
+}
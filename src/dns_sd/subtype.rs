@@ -0,0 +1,76 @@
+use anyhow::{bail, Result};
+
+use crate::packets::fqdn::{Label, MDNSFQDN};
+
+/// A service subtype such as `_printer`, see RFC 6763 section 7.1.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Subtype(String);
+
+impl Subtype {
+    /// Validates a subtype label: non-empty, at most 63 bytes and without dots.
+    pub fn new(subtype: &str) -> Result<Self> {
+        if subtype.is_empty() || subtype.len() > 63 {
+            bail!("Subtype '{subtype}' must be 1 to 63 bytes long");
+        }
+        if subtype.contains('.') {
+            bail!("Subtype '{subtype}' must be a single label");
+        }
+        if subtype.eq_ignore_ascii_case("_sub") {
+            bail!("'_sub' is not a valid subtype");
+        }
+
+        Ok(Subtype(subtype.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// `<subtype>._sub.<service>`, the name browsed for instances of the subtype.
+    pub fn to_fqdn(&self, service: &MDNSFQDN) -> MDNSFQDN {
+        let mut labels = vec![Label::String(self.0.clone()), Label::String("_sub".into())];
+        labels.extend(service.labels.iter().cloned());
+        MDNSFQDN { labels }
+    }
+
+    /// Splits `<subtype>._sub.<service>` into the subtype and the service name.
+    pub fn parse_fqdn(name: &MDNSFQDN) -> Result<(Subtype, MDNSFQDN)> {
+        let [Label::String(subtype), Label::String(sub), service @ ..] = &name.labels[..] else {
+            bail!("'{name}' is not a subtype name");
+        };
+        if !sub.eq_ignore_ascii_case("_sub") || service.is_empty() {
+            bail!("'{name}' is not a subtype name");
+        }
+
+        let service = MDNSFQDN {
+            labels: service.to_vec(),
+        };
+        Ok((Subtype::new(subtype)?, service))
+    }
+}
+
+impl std::fmt::Display for Subtype {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtype_names() {
+        let service = MDNSFQDN::new("_http._tcp.local");
+        let printer = Subtype::new("_printer").unwrap();
+        let name = printer.to_fqdn(&service);
+        assert_eq!(name, MDNSFQDN::new("_printer._sub._http._tcp.local"));
+        assert_eq!(Subtype::parse_fqdn(&name).unwrap(), (printer, service));
+
+        assert!(Subtype::parse_fqdn(&MDNSFQDN::new("_http._tcp.local")).is_err());
+        assert!(Subtype::parse_fqdn(&MDNSFQDN::new("_printer._sub")).is_err());
+        assert!(Subtype::new("").is_err());
+        assert!(Subtype::new("a.b").is_err());
+        assert!(Subtype::new(&"x".repeat(64)).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Weak,
+    },
+    time::Instant,
+};
+
+use crate::{
+    cache::RecordCache,
+    packets::{fqdn::MDNSFQDN, rdata::NSEC, CLASS_ANY, MDNSTYPE},
+    querier::QueryHandle,
+};
+
+/// Which addresses of a host to look up.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IpFamily {
+    V4,
+    V6,
+    Both,
+}
+
+impl IpFamily {
+    pub(crate) fn types(&self) -> Vec<MDNSTYPE> {
+        match self {
+            IpFamily::V4 => vec![MDNSTYPE::A],
+            IpFamily::V6 => vec![MDNSTYPE::AAAA],
+            IpFamily::Both => vec![MDNSTYPE::A, MDNSTYPE::AAAA],
+        }
+    }
+}
+
+/// The pending result of [`crate::querier::Querier::resolve_host`].
+#[derive(Debug)]
+pub struct HostLookup {
+    result: Receiver<Vec<IpAddr>>,
+    _token: Arc<()>,
+}
+
+impl HostLookup {
+    /// The addresses found, once the lookup has finished.
+    pub fn try_recv(&self) -> Option<Vec<IpAddr>> {
+        self.result.try_recv().ok()
+    }
+
+    pub fn result(&self) -> &Receiver<Vec<IpAddr>> {
+        &self.result
+    }
+}
+
+/// The querier's side of a [`HostLookup`].
+#[derive(Debug)]
+pub(crate) struct HostQuery {
+    name: MDNSFQDN,
+    types: Vec<MDNSTYPE>,
+    pub(crate) deadline: Option<Instant>,
+    _handles: Vec<QueryHandle>,
+    sender: Sender<Vec<IpAddr>>,
+    /// Dropped along with the [`HostLookup`].
+    owner: Weak<()>,
+}
+
+impl HostQuery {
+    pub(crate) fn new(
+        name: MDNSFQDN,
+        family: IpFamily,
+        deadline: Option<Instant>,
+        handles: Vec<QueryHandle>,
+    ) -> (Self, HostLookup) {
+        let (sender, result) = channel();
+        let token = Arc::new(());
+        let query = HostQuery {
+            name,
+            types: family.types(),
+            deadline,
+            _handles: handles,
+            sender,
+            owner: Arc::downgrade(&token),
+        };
+
+        (
+            query,
+            HostLookup {
+                result,
+                _token: token,
+            },
+        )
+    }
+
+    /// Sends the addresses once every family asked for has an answer, either addresses or an
+    /// NSEC record denying it, or the deadline has passed. Returns whether the lookup is done,
+    /// which it also is once the [`HostLookup`] is dropped.
+    pub(crate) fn poll(&self, cache: &RecordCache, now: Instant) -> bool {
+        if self.owner.strong_count() == 0 {
+            return true;
+        }
+
+        let mut addresses = vec![];
+        let mut answered = true;
+
+        for &rr_type in &self.types {
+            let found = cache
+                .lookup(&self.name, rr_type, CLASS_ANY, now)
+                .into_iter()
+                .filter_map(|e| address(e.record.rr_type, e.record.rdata()))
+                .collect::<Vec<_>>();
+
+            // An NSEC record for the name lists every type it has, so one without this type is a
+            // definitive negative answer, see RFC 6762 section 6.1.
+            let denied = cache
+                .lookup(&self.name, MDNSTYPE::NSEC, CLASS_ANY, now)
+                .into_iter()
+                .filter_map(|e| e.record.rdata_as::<NSEC>().ok())
+                .any(|nsec| !nsec.has(rr_type));
+
+            answered &= !found.is_empty() || denied;
+            addresses.extend(found);
+        }
+
+        let expired = self.deadline.is_some_and(|d| d <= now);
+        if !answered && !expired {
+            return false;
+        }
+
+        addresses.sort();
+        addresses.dedup();
+        let _ = self.sender.send(addresses);
+        true
+    }
+}
+
+/// Parses the rdata of an `A` or `AAAA` record.
+pub(crate) fn address(rr_type: MDNSTYPE, rdata: &[u8]) -> Option<IpAddr> {
+    match rr_type {
+        MDNSTYPE::A => Some(Ipv4Addr::from(<[u8; 4]>::try_from(rdata).ok()?).into()),
+        MDNSTYPE::AAAA => Some(Ipv6Addr::from(<[u8; 16]>::try_from(rdata).ok()?).into()),
+        _ => None,
+    }
+}
@@ -0,0 +1,12 @@
+//! Looking up and publishing host names and their addresses.
+
+pub use self::lookup::{HostLookup, IpFamily};
+pub use self::publish::{interface_addresses, HostInfo};
+pub use self::reverse::{parse_reverse_name, reverse_name, ReverseLookup};
+
+pub(crate) use self::lookup::{address, HostQuery};
+pub(crate) use self::reverse::ReverseQuery;
+
+mod lookup;
+mod publish;
+mod reverse;
@@ -0,0 +1,94 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
+use network_interface::{NetworkInterface, NetworkInterfaceConfig};
+
+use crate::{
+    dns_sd::HOST_TTL,
+    packets::{
+        fqdn::MDNSFQDN, pack::Packable, rdata::HINFO, resource_record::MDNSResourceRecord, MDNSTYPE,
+    },
+    responder::AuthoritativeRecord,
+};
+
+/// The identity of a host to publish as `<name>.local`, see RFC 6762 section 8.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HostInfo {
+    /// Such as `printer.local`.
+    pub name: MDNSFQDN,
+    pub addresses: Vec<IpAddr>,
+    pub hinfo: Option<HINFO>,
+}
+
+impl HostInfo {
+    /// The local machine, named after its host name and reachable on every interface address.
+    pub fn from_system() -> Result<Self> {
+        let hostname = hostname::get()?;
+        let hostname = hostname
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Host name is not valid UTF-8."))?;
+        // Only the first label of a possibly fully qualified host name is kept.
+        let label = hostname.split('.').next().unwrap_or_default();
+        if label.is_empty() {
+            anyhow::bail!("Host name is empty.");
+        }
+
+        Ok(HostInfo {
+            name: MDNSFQDN::new(&format!("{label}.local")),
+            addresses: interface_addresses()?,
+            hinfo: None,
+        })
+    }
+
+    /// Replaces the addresses with `addresses`, in any order, returning whether they changed.
+    pub fn set_addresses(&mut self, mut addresses: Vec<IpAddr>) -> bool {
+        addresses.sort();
+        addresses.dedup();
+        let mut current = self.addresses.clone();
+        current.sort();
+        if current == addresses {
+            return false;
+        }
+
+        self.addresses = addresses;
+        true
+    }
+
+    /// The unique address and `HINFO` records naming the host.
+    ///
+    /// The reverse-mapping PTR records are added by [`crate::responder::Responder::register`].
+    pub fn records(&self) -> Vec<AuthoritativeRecord> {
+        let record = |rr_type, rdata| {
+            let record =
+                MDNSResourceRecord::new(self.name.clone(), rr_type, false, HOST_TTL, rdata);
+            AuthoritativeRecord::unique(record)
+        };
+
+        let mut records = vec![];
+        for address in &self.addresses {
+            records.push(match address {
+                IpAddr::V4(v4) => record(MDNSTYPE::A, v4.octets().to_vec()),
+                IpAddr::V6(v6) => record(MDNSTYPE::AAAA, v6.octets().to_vec()),
+            });
+        }
+        if let Some(hinfo) = &self.hinfo {
+            records.push(record(MDNSTYPE::HINFO, hinfo.pack().into_vec()));
+        }
+
+        records
+    }
+}
+
+/// Every address of the machine's interfaces that other hosts could reach it on.
+pub fn interface_addresses() -> Result<Vec<IpAddr>> {
+    let mut addresses = NetworkInterface::show()?
+        .into_iter()
+        .flat_map(|interface| interface.addr)
+        .map(|a| a.ip())
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified() && !ip.is_multicast())
+        .collect::<Vec<_>>();
+    addresses.sort();
+    addresses.dedup();
+
+    Ok(addresses)
+}
@@ -0,0 +1,183 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Weak,
+    },
+    time::Instant,
+};
+
+use crate::{
+    cache::RecordCache,
+    packets::{
+        fqdn::{Label, MDNSFQDN},
+        CLASS_ANY, MDNSTYPE,
+    },
+    querier::QueryHandle,
+};
+
+/// The name of the PTR record mapping `address` back to a host name, `d.c.b.a.in-addr.arpa` for
+/// IPv4 and one label per nibble under `ip6.arpa` for IPv6, see RFC 6762 section 4.
+pub fn reverse_name(address: IpAddr) -> MDNSFQDN {
+    let labels = match address {
+        IpAddr::V4(v4) => {
+            let mut labels = v4.octets().map(|o| o.to_string()).to_vec();
+            labels.reverse();
+            labels.extend(["in-addr".into(), "arpa".into()]);
+            labels
+        }
+        IpAddr::V6(v6) => {
+            let mut labels = v6
+                .octets()
+                .iter()
+                .flat_map(|o| [o >> 4, o & 0xf])
+                .map(|n| format!("{n:x}"))
+                .collect::<Vec<_>>();
+            labels.reverse();
+            labels.extend(["ip6".into(), "arpa".into()]);
+            labels
+        }
+    };
+
+    MDNSFQDN {
+        labels: labels.into_iter().map(Label::String).collect(),
+    }
+}
+
+/// The address a reverse-mapping name such as `20.1.168.192.in-addr.arpa` stands for.
+pub fn parse_reverse_name(name: &MDNSFQDN) -> Option<IpAddr> {
+    let labels = name
+        .labels
+        .iter()
+        .map(|l| match l {
+            Label::String(s) => Some(s.to_ascii_lowercase()),
+            Label::Pointer(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    match &labels[..] {
+        [d, c, b, a, in_addr, arpa] if in_addr == "in-addr" && arpa == "arpa" => {
+            let [a, b, c, d] = [a, b, c, d].map(|o| o.parse::<u8>().ok());
+            Some(Ipv4Addr::new(a?, b?, c?, d?).into())
+        }
+        [nibbles @ .., ip6, arpa] if nibbles.len() == 32 && ip6 == "ip6" && arpa == "arpa" => {
+            let mut octets = [0u8; 16];
+            for (i, nibble) in nibbles.iter().rev().enumerate() {
+                if nibble.len() != 1 {
+                    return None;
+                }
+                let value = u8::from_str_radix(nibble, 16).ok()?;
+                octets[i / 2] |= if i % 2 == 0 { value << 4 } else { value };
+            }
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// The pending result of [`crate::querier::Querier::resolve_address`].
+#[derive(Debug)]
+pub struct ReverseLookup {
+    result: Receiver<Vec<MDNSFQDN>>,
+    _token: Arc<()>,
+}
+
+impl ReverseLookup {
+    /// The host names found, once the lookup has finished.
+    pub fn try_recv(&self) -> Option<Vec<MDNSFQDN>> {
+        self.result.try_recv().ok()
+    }
+
+    pub fn result(&self) -> &Receiver<Vec<MDNSFQDN>> {
+        &self.result
+    }
+}
+
+/// The querier's side of a [`ReverseLookup`].
+#[derive(Debug)]
+pub(crate) struct ReverseQuery {
+    name: MDNSFQDN,
+    pub(crate) deadline: Option<Instant>,
+    _handle: QueryHandle,
+    sender: Sender<Vec<MDNSFQDN>>,
+    /// Dropped along with the [`ReverseLookup`].
+    owner: Weak<()>,
+}
+
+impl ReverseQuery {
+    pub(crate) fn new(
+        name: MDNSFQDN,
+        deadline: Option<Instant>,
+        handle: QueryHandle,
+    ) -> (Self, ReverseLookup) {
+        let (sender, result) = channel();
+        let token = Arc::new(());
+        let query = ReverseQuery {
+            name,
+            deadline,
+            _handle: handle,
+            sender,
+            owner: Arc::downgrade(&token),
+        };
+
+        (
+            query,
+            ReverseLookup {
+                result,
+                _token: token,
+            },
+        )
+    }
+
+    /// Sends the host names once any are cached, or whatever is known at the deadline. Returns
+    /// whether the lookup is done, which it also is once the [`ReverseLookup`] is dropped.
+    pub(crate) fn poll(&self, cache: &RecordCache, now: Instant) -> bool {
+        if self.owner.strong_count() == 0 {
+            return true;
+        }
+
+        let hosts = cache
+            .lookup(&self.name, MDNSTYPE::PTR, CLASS_ANY, now)
+            .into_iter()
+            .filter_map(|e| e.record.rdata_as::<MDNSFQDN>().ok())
+            .collect::<Vec<_>>();
+
+        if hosts.is_empty() && self.deadline.is_none_or(|d| d > now) {
+            return false;
+        }
+
+        let _ = self.sender.send(hosts);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_names() {
+        let v4 = IpAddr::from([192, 168, 1, 20]);
+        let name = reverse_name(v4);
+        assert_eq!(name, MDNSFQDN::new("20.1.168.192.in-addr.arpa"));
+        assert_eq!(parse_reverse_name(&name), Some(v4));
+
+        let v6 = "fe80::1:abcd".parse::<IpAddr>().unwrap();
+        let name = reverse_name(v6);
+        assert_eq!(
+            name.to_string(),
+            "d.c.b.a.1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.e.f.ip6.arpa"
+        );
+        assert_eq!(parse_reverse_name(&name), Some(v6));
+
+        assert_eq!(
+            parse_reverse_name(&MDNSFQDN::new("1.2.3.in-addr.arpa")),
+            None
+        );
+        assert_eq!(
+            parse_reverse_name(&MDNSFQDN::new("300.1.168.192.in-addr.arpa")),
+            None
+        );
+        assert_eq!(parse_reverse_name(&MDNSFQDN::new("printer.local")), None);
+    }
+}
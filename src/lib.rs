@@ -1,4 +1,11 @@
+pub mod cache;
+pub mod dns_sd;
+pub mod host;
 pub mod packets;
+pub mod querier;
+pub mod responder;
+pub mod socket;
+pub mod transmit;
 
 pub type Data = bitvec::vec::BitVec<u8, bitvec::order::Msb0>;
 #[allow(non_camel_case_types)]
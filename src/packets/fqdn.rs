@@ -1,16 +1,21 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bitvec::{order::Msb0, vec::BitVec, view::BitView};
 use std::{collections::HashMap, fmt::Debug};
 
 use super::pack::Packable;
 use crate::load;
 
-#[derive(PartialEq, Eq, Clone)]
+/// How many compression pointers may be followed while resolving a single name, so that pointer
+/// loops in a malformed packet end in an error.
+const MAX_POINTER_DEPTH: usize = 32;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub struct MDNSFQDN {
     pub labels: Vec<Label>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Label {
     String(String),
     Pointer(u16),
@@ -19,60 +24,106 @@ pub enum Label {
 impl MDNSFQDN {
     pub fn new(s: &str) -> Self {
         MDNSFQDN {
-            labels: s.split('.').map(|s| Label::String(s.to_string())).collect(),
+            labels: s
+                .trim_end_matches('.')
+                .split('.')
+                .map(|s| Label::String(s.to_string()))
+                .collect(),
         }
     }
 
-    pub fn to_string(&self) -> String {
-        let strings = self
-            .labels
-            .iter()
-            .cloned()
-            .map(|s| match s {
-                Label::String(s) => s,
-                Label::Pointer(p) => format!("<<#{p}>>"),
-            })
-            .collect::<Vec<_>>();
+    pub fn get_labels(&self) -> Vec<Label> {
+        self.labels.clone()
+    }
 
-        strings.join(".")
+    /// Returns a copy of this name with every label lowercased, as DNS names compare
+    /// case-insensitively.
+    pub fn to_ascii_lowercase(&self) -> Self {
+        MDNSFQDN {
+            labels: self
+                .labels
+                .iter()
+                .map(|label| match label {
+                    Label::String(s) => Label::String(s.to_ascii_lowercase()),
+                    Label::Pointer(p) => Label::Pointer(*p),
+                })
+                .collect(),
+        }
     }
 
-    pub fn get_labels(&self) -> Vec<Label> {
-        self.labels.clone()
+    pub fn eq_ignore_ascii_case(&self, other: &MDNSFQDN) -> bool {
+        self.to_ascii_lowercase() == other.to_ascii_lowercase()
     }
 
     pub fn resolve(
         &mut self,
         data: &crate::Data,
-        data_cache: &mut HashMap<usize, String>,
+        data_cache: &mut HashMap<usize, MDNSFQDN>,
         pointer_idx: Option<usize>,
-    ) -> &mut Self {
-        if pointer_idx.is_some() && data_cache.contains_key(&pointer_idx.unwrap()) {
-            *self = MDNSFQDN::new(&data_cache.get(&pointer_idx.unwrap()).unwrap().clone());
+    ) -> Result<&mut Self> {
+        self.resolve_with_depth(data, data_cache, pointer_idx, 0)
+    }
+
+    fn resolve_with_depth(
+        &mut self,
+        data: &crate::Data,
+        data_cache: &mut HashMap<usize, MDNSFQDN>,
+        pointer_idx: Option<usize>,
+        depth: usize,
+    ) -> Result<&mut Self> {
+        if depth > MAX_POINTER_DEPTH {
+            bail!("Too many compression pointers while resolving a name.");
+        }
+        if let Some(cached) = pointer_idx.and_then(|idx| data_cache.get(&idx)) {
+            *self = cached.clone();
+        }
+
+        let mut labels = vec![];
+        for label in std::mem::take(&mut self.labels) {
+            match label {
+                Label::Pointer(p) => {
+                    let start = p as usize * 8;
+                    if start >= data.len() {
+                        bail!(
+                            "Compression pointer {} points past the end of the packet.",
+                            p
+                        );
+                    }
+                    let mut name = MDNSFQDN::unpack(&mut data[start..].to_bitvec())?;
+                    name.resolve_with_depth(data, data_cache, Some(p as usize), depth + 1)?;
+                    labels.extend(name.labels);
+                }
+                label => labels.push(label),
+            }
         }
+        self.labels = labels;
 
-        self.labels.iter_mut().for_each(|label| {
-            if let Label::Pointer(p) = label {
-                *label = Label::String(
-                    MDNSFQDN::unpack(&mut data[(*p as usize) * 8..].to_bitvec())
-                        .unwrap()
-                        .resolve(&data, data_cache, Some(*p as usize))
-                        .to_string(),
-                )
-            };
-        });
-
-        if pointer_idx.is_some() && !data_cache.contains_key(&pointer_idx.unwrap()) {
-            data_cache.insert(pointer_idx.unwrap(), self.to_string());
+        if let Some(idx) = pointer_idx {
+            data_cache.entry(idx).or_insert_with(|| self.clone());
         }
 
-        self
+        Ok(self)
+    }
+}
+
+impl std::fmt::Display for MDNSFQDN {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let strings = self
+            .labels
+            .iter()
+            .map(|s| match s {
+                Label::String(s) => s.clone(),
+                Label::Pointer(p) => format!("<<#{p}>>"),
+            })
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", strings.join("."))
     }
 }
 
 impl Debug for MDNSFQDN {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "MDNSFQDN({})", self.to_string())
+        write!(f, "MDNSFQDN({})", self)
     }
 }
 
@@ -91,7 +142,7 @@ impl Packable for MDNSFQDN {
                 }
             }
         }
-        if let Label::String(_) = self.labels.last().unwrap() {
+        if !matches!(self.labels.last(), Some(Label::Pointer(_))) {
             data.extend_from_bitslice(0u8.view_bits::<Msb0>());
         }
         data
@@ -100,23 +151,26 @@ impl Packable for MDNSFQDN {
     fn unpack(data: &mut crate::Data) -> Result<Self> {
         let mut labels = vec![];
 
-        while data[..8].any() {
+        loop {
             let len = load!(data => u8) as usize;
+            // The terminating zero.
+            if len == 0 {
+                break;
+            }
             if len & 0b1100_0000 == 0b1100_0000 {
                 let pointer = ((len as u16 & 0b0011_1111) << 8) | load!(data => u8) as u16;
                 labels.push(Label::Pointer(pointer));
                 break;
             }
+            if data.len() < len * 8 {
+                bail!("Label of {} bytes runs past the end of the data.", len);
+            }
 
-            let label =
-                String::from_utf8((0..len).map(|_| load!(data => u8)).collect::<Vec<_>>()).unwrap();
-            labels.push(Label::String(label));
-        }
-        // For the terminating zero.
-        if !labels.is_empty() {
-            if let Label::String(_) = labels.last().unwrap() {
-                data.drain(..8);
+            let mut bytes = Vec::with_capacity(len);
+            for _ in 0..len {
+                bytes.push(load!(data => u8));
             }
+            labels.push(Label::String(String::from_utf8(bytes)?));
         }
         let fqdn = MDNSFQDN { labels };
 
@@ -2,6 +2,13 @@ use super::{pack::Packable, util::read_u16s_be};
 use crate::concat_packable_bits;
 use anyhow::Result;
 
+/// Query/response bit, set on responses.
+pub const FLAG_QR: u16 = 1 << 15;
+/// Authoritative answer bit, set on all mDNS responses.
+pub const FLAG_AA: u16 = 1 << 10;
+/// Truncated bit, set on queries whose known answers continue in a following packet.
+pub const FLAG_TC: u16 = 1 << 9;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct MDNSHeader {
     pub transaction_id: u16,
@@ -29,6 +36,14 @@ impl MDNSHeader {
     pub fn new() -> Self {
         Default::default()
     }
+
+    pub fn is_response(&self) -> bool {
+        self.flags & FLAG_QR != 0
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.flags & FLAG_TC != 0
+    }
 }
 
 impl Packable for MDNSHeader {
@@ -45,7 +60,7 @@ impl Packable for MDNSHeader {
 
     fn unpack(data: &mut crate::Data) -> Result<Self> {
         let [transaction_id, flags, questions, answer_rrs, authority_rrs, additional_rrs] =
-            read_u16s_be::<6>(data).expect("Failed to read u16s from data.");
+            read_u16s_be::<6>(data)?;
 
         let header = MDNSHeader {
             transaction_id,
@@ -3,19 +3,28 @@ use anyhow::Result;
 use self::pack::Packable;
 use crate::load;
 
+pub mod fqdn;
+pub mod header;
 pub mod pack;
 pub mod packet;
+pub mod query;
+pub mod rdata;
+pub mod resource_record;
 pub mod response;
 
-mod fqdn;
-mod header;
-mod query;
-mod resource_record;
 mod util;
 
+// CONSTANTS
+
+pub const CLASS_IN: u16 = 1;
+pub const CLASS_ANY: u16 = 255;
+/// The top bit of a record's class field (cache-flush) or a question's class field
+/// (unicast-response).
+pub const CLASS_TOP_BIT: u16 = 1 << 15;
+
 // ENUMS
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum MDNSTYPE {
     // RESOURCE RECORDS
     A = 1,
@@ -72,11 +81,13 @@ pub enum MDNSTYPE {
     OPT = 41,
 }
 
-impl From<u16> for MDNSTYPE {
-    fn from(value: u16) -> Self {
+impl TryFrom<u16> for MDNSTYPE {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u16) -> Result<Self> {
         use MDNSTYPE::*;
 
-        match value {
+        let ty = match value {
             // RESOURCE RECORDS
             1 => A,
             28 => AAAA,
@@ -89,7 +100,7 @@ impl From<u16> for MDNSTYPE {
             5 => CNAME,
             62 => CSYNC,
             49 => DHCID,
-            3276 => DLV,
+            32769 => DLV,
             39 => DNAME,
             48 => DNSKEY,
             43 => DS,
@@ -130,8 +141,10 @@ impl From<u16> for MDNSTYPE {
             252 => AXFR,
             251 => IXFR,
             41 => OPT,
-            _ => panic!("Invalid MDNSTYPE received: {}", value),
-        }
+            _ => anyhow::bail!("Unknown MDNSTYPE received: {}", value),
+        };
+
+        Ok(ty)
     }
 }
 
@@ -141,8 +154,6 @@ impl Packable for MDNSTYPE {
     }
 
     fn unpack(data: &mut crate::Data) -> Result<Self> {
-        let ty = load!(data => u16).into();
-
-        Ok(ty)
+        MDNSTYPE::try_from(load!(data => u16))
     }
 }
@@ -12,11 +12,15 @@ where
     T: Packable + Clone + Copy + Default,
 {
     fn pack(&self) -> crate::Data {
-        BitVec::from_iter(self.into_iter().flat_map(|e| e.pack()))
+        BitVec::from_iter(self.iter().flat_map(|e| e.pack()))
     }
 
     fn unpack(data: &mut crate::Data) -> Result<Self> {
-        Ok([T::default(); N].map(|_| T::unpack(data).unwrap()))
+        let mut out = [T::default(); N];
+        for value in &mut out {
+            *value = T::unpack(data)?;
+        }
+        Ok(out)
     }
 }
 
@@ -25,7 +29,7 @@ where
     T: Packable,
 {
     fn pack(&self) -> crate::Data {
-        BitVec::from_iter(self.into_iter().flat_map(|e| e.pack()))
+        BitVec::from_iter(self.iter().flat_map(|e| e.pack()))
     }
 
     fn unpack(_data: &mut crate::Data) -> Result<Self> {
@@ -43,7 +47,8 @@ impl Packable for bool {
     }
 
     fn unpack(data: &mut crate::Data) -> Result<Self> {
-        Ok(data.pop().unwrap())
+        data.pop()
+            .ok_or_else(|| anyhow::anyhow!("Expected a bit, found none."))
     }
 }
 
@@ -54,12 +59,12 @@ macro_rules! impl_packable_for_int {
     ($($t:ty),*) => {
         $(
             impl Packable for $t {
-                fn pack(&self) -> crate::Data {
+                fn pack(&self) -> $crate::Data {
                     self.to_be_bytes().view_bits().to_bitvec()
                 }
 
-                fn unpack(data: &mut crate::Data) -> Result<Self> {
-                    Ok(data.drain(..std::mem::size_of::<$t>() * 8).as_bitslice().load_be::<$t>())
+                fn unpack(data: &mut $crate::Data) -> Result<Self> {
+                    Ok($crate::load!(data => $t))
                 }
             }
         )*
@@ -2,12 +2,21 @@ use anyhow::Result;
 
 use super::{pack::Packable, util::read_vec_of_t};
 
-use super::{header::MDNSHeader, query::MDNSQuery, MDNSTYPE};
+use super::{
+    header::{MDNSHeader, FLAG_TC},
+    query::MDNSQuery,
+    resource_record::MDNSResourceRecord,
+    MDNSTYPE,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct MDNSPacket {
     header: MDNSHeader,
     queries: Vec<MDNSQuery>,
+    /// Records the querier already knows, see RFC 6762 section 7.1.
+    known_answers: Vec<MDNSResourceRecord>,
+    /// Records being probed for, see RFC 6762 section 8.2.
+    authorities: Vec<MDNSResourceRecord>,
 }
 
 impl MDNSPacket {
@@ -17,22 +26,83 @@ impl MDNSPacket {
         MDNSPacket {
             header,
             queries: vec![query],
+            known_answers: vec![],
+            authorities: vec![],
         }
     }
+
+    pub fn with_queries(queries: Vec<MDNSQuery>) -> Self {
+        let header = MDNSHeader {
+            questions: queries.len() as u16,
+            ..MDNSHeader::new()
+        };
+        MDNSPacket {
+            header,
+            queries,
+            known_answers: vec![],
+            authorities: vec![],
+        }
+    }
+
+    pub fn with_known_answers(mut self, known_answers: Vec<MDNSResourceRecord>) -> Self {
+        self.header.answer_rrs = known_answers.len() as u16;
+        self.known_answers = known_answers;
+        self
+    }
+
+    pub fn with_authorities(mut self, authorities: Vec<MDNSResourceRecord>) -> Self {
+        self.header.authority_rrs = authorities.len() as u16;
+        self.authorities = authorities;
+        self
+    }
+
+    /// Marks that more known answers follow in another packet, see RFC 6762 section 7.2.
+    pub fn set_truncated(&mut self, truncated: bool) {
+        if truncated {
+            self.header.flags |= FLAG_TC;
+        } else {
+            self.header.flags &= !FLAG_TC;
+        }
+    }
+
+    pub fn header(&self) -> &MDNSHeader {
+        &self.header
+    }
+
+    pub fn queries(&self) -> &[MDNSQuery] {
+        &self.queries
+    }
+
+    pub fn known_answers(&self) -> &[MDNSResourceRecord] {
+        &self.known_answers
+    }
+
+    pub fn authorities(&self) -> &[MDNSResourceRecord] {
+        &self.authorities
+    }
 }
 
 impl Packable for MDNSPacket {
     fn pack(&self) -> crate::Data {
         let mut out = self.header.pack();
         out.extend(self.queries.pack());
+        out.extend(self.known_answers.pack());
+        out.extend(self.authorities.pack());
         out
     }
 
     fn unpack(data: &mut crate::Data) -> Result<Self> {
         let header = MDNSHeader::unpack(data)?;
         let queries = read_vec_of_t(data, header.questions as usize)?;
+        let known_answers = read_vec_of_t(data, header.answer_rrs as usize)?;
+        let authorities = read_vec_of_t(data, header.authority_rrs as usize)?;
 
-        let packet = MDNSPacket { header, queries };
+        let packet = MDNSPacket {
+            header,
+            queries,
+            known_answers,
+            authorities,
+        };
 
         Ok(packet)
     }
@@ -71,7 +141,7 @@ mod tests {
             // Query
             let query = {
                 let qname = b"\x05_http\x04_tcp\x05local\x00";
-                let len = qname.len() * 8 as usize;
+                let len = qname.len() * 8;
 
                 let qtype: u16 = 0x000c; // PTR
                 let unicast_response: u16 = 1u16 << 15;
@@ -79,7 +149,7 @@ mod tests {
 
                 let mut out = bitvec![u8, Msb0; 0; len + 16 * 2];
 
-                out[00..len].copy_from_bitslice(&qname.view_bits::<Msb0>());
+                out[00..len].copy_from_bitslice(qname.view_bits::<Msb0>());
                 out[len..len + 16].store_be(qtype);
                 out[len + 16..len + 32].store_be(unicast_response | qclass);
 
@@ -4,7 +4,7 @@ use anyhow::Result;
 
 use crate::{bool_u15, concat_packable_bits, unpack_chain};
 
-use super::{fqdn::MDNSFQDN, pack::Packable, MDNSTYPE};
+use super::{fqdn::MDNSFQDN, pack::Packable, CLASS_IN, CLASS_TOP_BIT, MDNSTYPE};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct MDNSQuery {
@@ -24,8 +24,35 @@ impl MDNSQuery {
         }
     }
 
-    pub fn resolve(&mut self, data: &crate::Data, data_cache: &mut HashMap<usize, String>) {
-        self.qname.resolve(data, data_cache, None);
+    /// Creates an `IN` class query, asking for a unicast response if `unicast_response` is set.
+    pub fn from_fqdn(qname: MDNSFQDN, qtype: MDNSTYPE, unicast_response: bool) -> Self {
+        let qu_qclass = if unicast_response {
+            CLASS_TOP_BIT | CLASS_IN
+        } else {
+            CLASS_IN
+        };
+        MDNSQuery {
+            qname,
+            qtype,
+            qu_qclass,
+        }
+    }
+
+    pub fn class(&self) -> u16 {
+        self.qu_qclass & !CLASS_TOP_BIT
+    }
+
+    pub fn unicast_response(&self) -> bool {
+        self.qu_qclass & CLASS_TOP_BIT != 0
+    }
+
+    pub fn resolve(
+        &mut self,
+        data: &crate::Data,
+        data_cache: &mut HashMap<usize, MDNSFQDN>,
+    ) -> Result<()> {
+        self.qname.resolve(data, data_cache, None)?;
+        Ok(())
     }
 }
 
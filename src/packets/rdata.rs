@@ -0,0 +1,317 @@
+use anyhow::Result;
+
+use super::{fqdn::MDNSFQDN, pack::Packable, util::read_u16s_be, MDNSTYPE};
+use crate::concat_packable_bits;
+
+/// The rdata of an `SRV` record, see RFC 2782.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SRV {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: MDNSFQDN,
+}
+
+impl Packable for SRV {
+    fn pack(&self) -> crate::Data {
+        concat_packable_bits![self.priority, self.weight, self.port, self.target]
+    }
+
+    fn unpack(data: &mut crate::Data) -> Result<Self> {
+        let [priority, weight, port] = read_u16s_be::<3>(data)?;
+        let target = MDNSFQDN::unpack(data)?;
+
+        let srv = SRV {
+            priority,
+            weight,
+            port,
+            target,
+        };
+
+        Ok(srv)
+    }
+}
+
+/// The rdata of a `TXT` record, a series of length-prefixed strings, see RFC 6763 section 6.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct TXT {
+    strings: Vec<Vec<u8>>,
+}
+
+impl TXT {
+    /// Fails if any string is longer than the 255 bytes its length prefix can express.
+    pub fn new(strings: Vec<Vec<u8>>) -> Result<Self> {
+        if let Some(string) = strings.iter().find(|s| s.len() > u8::MAX as usize) {
+            anyhow::bail!(
+                "TXT string of {} bytes is over 255 bytes long",
+                string.len()
+            );
+        }
+
+        Ok(TXT { strings })
+    }
+
+    /// Builds `key=value` strings, or just `key` for attributes without a value.
+    pub fn from_pairs<'a>(
+        pairs: impl IntoIterator<Item = (&'a str, Option<&'a [u8]>)>,
+    ) -> Result<Self> {
+        let strings = pairs
+            .into_iter()
+            .map(|(key, value)| match value {
+                Some(value) => [key.as_bytes(), b"=", value].concat(),
+                None => key.as_bytes().to_vec(),
+            })
+            .collect();
+
+        TXT::new(strings)
+    }
+
+    pub fn strings(&self) -> &[Vec<u8>] {
+        &self.strings
+    }
+
+    /// The key/value pairs, skipping empty strings, see RFC 6763 section 6.4.
+    pub fn pairs(&self) -> Vec<(String, Option<Vec<u8>>)> {
+        self.strings
+            .iter()
+            .filter(|s| !s.is_empty() && s[0] != b'=')
+            .map(|s| match s.iter().position(|b| *b == b'=') {
+                Some(i) => (
+                    String::from_utf8_lossy(&s[..i]).into_owned(),
+                    Some(s[i + 1..].to_vec()),
+                ),
+                None => (String::from_utf8_lossy(s).into_owned(), None),
+            })
+            .collect()
+    }
+
+    /// The value of `key`, compared case-insensitively, if present. The first occurrence wins.
+    pub fn get(&self, key: &str) -> Option<Option<Vec<u8>>> {
+        self.pairs()
+            .into_iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+}
+
+impl Packable for TXT {
+    fn pack(&self) -> crate::Data {
+        // An empty TXT record still holds a single empty string, see RFC 6763 section 6.1.
+        if self.strings.is_empty() {
+            return 0u8.pack();
+        }
+
+        let mut data = crate::Data::new();
+        for string in &self.strings {
+            data.extend((string.len() as u8).pack());
+            data.extend(string.iter().flat_map(|b| b.pack()));
+        }
+        data
+    }
+
+    fn unpack(data: &mut crate::Data) -> Result<Self> {
+        let mut strings = vec![];
+
+        while data.len() >= 8 {
+            let len = u8::unpack(data)? as usize;
+            if data.len() < len * 8 {
+                anyhow::bail!("TXT string longer than the remaining rdata");
+            }
+            strings.push((0..len).map(|_| u8::unpack(data)).collect::<Result<_>>()?);
+        }
+        strings.retain(|s: &Vec<u8>| !s.is_empty());
+
+        Ok(TXT { strings })
+    }
+}
+
+/// The rdata of an `HINFO` record, describing a host's hardware and operating system, see RFC 1035
+/// section 3.3.2.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HINFO {
+    cpu: String,
+    os: String,
+}
+
+impl HINFO {
+    /// Fails if either string is longer than the 255 bytes its length prefix can express.
+    pub fn new(cpu: impl Into<String>, os: impl Into<String>) -> Result<Self> {
+        let (cpu, os) = (cpu.into(), os.into());
+        if let Some(string) = [&cpu, &os].into_iter().find(|s| s.len() > u8::MAX as usize) {
+            anyhow::bail!(
+                "HINFO string of {} bytes is over 255 bytes long",
+                string.len()
+            );
+        }
+
+        Ok(HINFO { cpu, os })
+    }
+
+    pub fn cpu(&self) -> &str {
+        &self.cpu
+    }
+
+    pub fn os(&self) -> &str {
+        &self.os
+    }
+}
+
+impl Packable for HINFO {
+    fn pack(&self) -> crate::Data {
+        let mut data = crate::Data::new();
+        for string in [&self.cpu, &self.os] {
+            data.extend((string.len() as u8).pack());
+            data.extend(string.bytes().flat_map(|b| b.pack()));
+        }
+        data
+    }
+
+    fn unpack(data: &mut crate::Data) -> Result<Self> {
+        let mut strings = [String::new(), String::new()];
+        for string in &mut strings {
+            let len = u8::unpack(data)? as usize;
+            if data.len() < len * 8 {
+                anyhow::bail!("HINFO string longer than the remaining rdata");
+            }
+            let bytes = (0..len)
+                .map(|_| u8::unpack(data))
+                .collect::<Result<Vec<_>>>()?;
+            *string = String::from_utf8_lossy(&bytes).into_owned();
+        }
+        let [cpu, os] = strings;
+
+        Ok(HINFO { cpu, os })
+    }
+}
+
+/// The rdata of an `NSEC` record, which in mDNS asserts which types exist for a name, see RFC 6762
+/// section 6.1.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NSEC {
+    pub next: MDNSFQDN,
+    /// Raw type numbers, as the bitmap may well name types `MDNSTYPE` doesn't know.
+    pub types: Vec<u16>,
+}
+
+impl NSEC {
+    pub fn has(&self, rr_type: MDNSTYPE) -> bool {
+        self.types.contains(&(rr_type as u16))
+    }
+}
+
+impl Packable for NSEC {
+    fn pack(&self) -> crate::Data {
+        let mut data = self.next.pack();
+
+        /* https://www.rfc-editor.org/rfc/rfc4034.html#section-4.1.2
+            The RR type space is split into 256 window blocks, each representing
+            the low-order 8 bits of the 16-bit RR type space. Each block that has
+            at least one active RR type is encoded using a single octet window
+            number (from 0 to 255), a single octet bitmap length (from 1 to 32)
+            indicating the number of octets used for the window block's bitmap,
+            and up to 32 octets (256 bits) of bitmap.
+        */
+        let mut types = self.types.clone();
+        types.sort();
+        types.dedup();
+        for window in 0..=255u8 {
+            let mut bitmap = [0u8; 32];
+            let mut length = 0;
+            for ty in types.iter().filter(|t| (*t >> 8) as u8 == window) {
+                let bit = (ty & 0xff) as usize;
+                bitmap[bit / 8] |= 0b1000_0000 >> (bit % 8);
+                length = bit / 8 + 1;
+            }
+            if length > 0 {
+                data.extend(window.pack());
+                data.extend((length as u8).pack());
+                data.extend(bitmap[..length].iter().flat_map(|b| b.pack()));
+            }
+        }
+
+        data
+    }
+
+    fn unpack(data: &mut crate::Data) -> Result<Self> {
+        let next = MDNSFQDN::unpack(data)?;
+
+        let mut types = vec![];
+        while data.len() >= 16 {
+            let window = u8::unpack(data)? as u16;
+            let length = u8::unpack(data)? as usize;
+            if length > 32 || data.len() < length * 8 {
+                anyhow::bail!("NSEC bitmap longer than the remaining rdata");
+            }
+            for byte in 0..length {
+                let bits = u8::unpack(data)?;
+                for bit in 0..8 {
+                    if bits & (0b1000_0000 >> bit) != 0 {
+                        types.push(window << 8 | (byte * 8 + bit) as u16);
+                    }
+                }
+            }
+        }
+
+        Ok(NSEC { next, types })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_txt() {
+        let txt = TXT::from_pairs([("path", Some(&b"/admin"[..])), ("secure", None)]).unwrap();
+        let unpacked = TXT::unpack(&mut txt.pack()).unwrap();
+        assert_eq!(unpacked, txt);
+        assert_eq!(unpacked.get("PATH"), Some(Some(b"/admin".to_vec())));
+        assert_eq!(unpacked.get("secure"), Some(None));
+        assert_eq!(unpacked.get("missing"), None);
+
+        assert_eq!(TXT::default().pack().len(), 8);
+        assert_eq!(
+            TXT::unpack(&mut TXT::default().pack()).unwrap(),
+            TXT::default()
+        );
+
+        // Longer strings don't fit their length prefix.
+        assert!(TXT::from_pairs([("key", Some(&[b'a'; 251][..]))]).is_ok());
+        assert!(TXT::from_pairs([("key", Some(&[b'a'; 252][..]))]).is_err());
+    }
+
+    #[test]
+    fn test_hinfo() {
+        let hinfo = HINFO::new("x86_64", "linux").unwrap();
+        assert_eq!(HINFO::unpack(&mut hinfo.pack()).unwrap(), hinfo);
+
+        // Longer strings don't fit their length prefix.
+        assert!(HINFO::new("a".repeat(255), "linux").is_ok());
+        assert!(HINFO::new("x86_64", "é".repeat(128)).is_err());
+    }
+
+    #[test]
+    fn test_nsec() {
+        let nsec = NSEC {
+            next: MDNSFQDN::new("printer.local"),
+            types: vec![
+                MDNSTYPE::A as u16,
+                MDNSTYPE::TXT as u16,
+                MDNSTYPE::NSEC as u16,
+                1234,
+            ],
+        };
+        let mut data = nsec.pack();
+        // Window 0 is 6 bytes long for NSEC, window 4 one byte for type 1234.
+        assert_eq!(data.len() / 8, nsec.next.pack().len() / 8 + 2 + 6 + 2 + 27);
+
+        let unpacked = NSEC::unpack(&mut data).unwrap();
+        assert_eq!(unpacked, nsec);
+        assert!(unpacked.has(MDNSTYPE::A));
+        assert!(!unpacked.has(MDNSTYPE::AAAA));
+    }
+}
@@ -8,7 +8,7 @@ use crate::{bool_u15, concat_packable_bits, unpack_chain};
 use super::{
     fqdn::{Label, MDNSFQDN},
     pack::Packable,
-    MDNSTYPE,
+    CLASS_IN, CLASS_TOP_BIT, MDNSTYPE,
 };
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -17,25 +17,117 @@ pub struct MDNSResourceRecord {
     pub rr_type: MDNSTYPE,
     pub cache_flush_rr_class: bool_u15,
     pub ttl: u32,
-    /// The length of the r_data field in bytes, prior to decompression.
+    /// The length of the r_data field in bytes, updated once any trailing pointer is decompressed.
     pub rd_length: u16,
     /// A hacky way to represent a possibly pointer terminated section of data.
     pub r_data: (Vec<u8>, Option<Label>),
 }
 
 impl MDNSResourceRecord {
-    pub fn resolve(&mut self, data: &crate::Data, data_cache: &mut HashMap<usize, String>) {
-        self.rr_name.resolve(data, data_cache, None);
+    /// Creates an `IN` class record from uncompressed rdata.
+    pub fn new(
+        rr_name: MDNSFQDN,
+        rr_type: MDNSTYPE,
+        cache_flush: bool,
+        ttl: u32,
+        r_data: Vec<u8>,
+    ) -> Self {
+        let cache_flush_rr_class = if cache_flush {
+            CLASS_TOP_BIT | CLASS_IN
+        } else {
+            CLASS_IN
+        };
+
+        MDNSResourceRecord {
+            rr_name,
+            rr_type,
+            cache_flush_rr_class,
+            ttl,
+            rd_length: r_data.len() as u16,
+            r_data: (r_data, None),
+        }
+    }
+
+    pub fn class(&self) -> u16 {
+        self.cache_flush_rr_class & !CLASS_TOP_BIT
+    }
+
+    pub fn cache_flush(&self) -> bool {
+        self.cache_flush_rr_class & CLASS_TOP_BIT != 0
+    }
+
+    pub fn rdata(&self) -> &[u8] {
+        &self.r_data.0
+    }
+
+    /// Parses the rdata as `T`, such as an `MDNSFQDN` for `PTR` records.
+    pub fn rdata_as<T: Packable>(&self) -> Result<T> {
+        T::unpack(&mut self.r_data.0.view_bits::<Msb0>().to_bitvec())
+    }
+
+    /// Whether both records hold the same data, regardless of TTL and cache-flush bit.
+    pub fn same_data(&self, other: &MDNSResourceRecord) -> bool {
+        self.rr_type == other.rr_type
+            && self.class() == other.class()
+            && self.rdata() == other.rdata()
+            && self.rr_name.eq_ignore_ascii_case(&other.rr_name)
+    }
+
+    pub fn resolve(
+        &mut self,
+        data: &crate::Data,
+        data_cache: &mut HashMap<usize, MDNSFQDN>,
+    ) -> Result<()> {
+        self.rr_name.resolve(data, data_cache, None)?;
         if let Some(ptr) = self.r_data.1.clone() {
             self.r_data.0.extend(
                 MDNSFQDN { labels: vec![ptr] }
-                    .resolve(data, data_cache, None)
-                    .to_string()
-                    .as_bytes()
-                    .to_vec(),
+                    .resolve(data, data_cache, None)?
+                    .pack()
+                    .into_vec(),
             );
             self.r_data.1 = None;
+            self.rd_length = self.r_data.0.len() as u16;
         };
+        if self.rr_type == MDNSTYPE::NSEC {
+            self.resolve_leading_name(data, data_cache)?;
+        }
+        Ok(())
+    }
+
+    /// NSEC rdata starts with a possibly compressed name and continues with the type bitmaps, so
+    /// the pointer isn't at the end like for the other types.
+    fn resolve_leading_name(
+        &mut self,
+        data: &crate::Data,
+        data_cache: &mut HashMap<usize, MDNSFQDN>,
+    ) -> Result<()> {
+        let rdata = &self.r_data.0;
+        let mut i = 0;
+        while let Some(&len) = rdata.get(i) {
+            if len == 0 {
+                return Ok(());
+            }
+            if len & 0b1100_0000 == 0b1100_0000 {
+                break;
+            }
+            i += 1 + len as usize;
+        }
+        if i + 2 > rdata.len() {
+            return Ok(());
+        }
+
+        let Ok(mut name) = MDNSFQDN::unpack(&mut rdata[..i + 2].view_bits::<Msb0>().to_bitvec())
+        else {
+            return Ok(());
+        };
+        let mut resolved = name.resolve(data, data_cache, None)?.pack().into_vec();
+        resolved.extend_from_slice(&rdata[i + 2..]);
+
+        self.rd_length = resolved.len() as u16;
+        self.r_data.0 = resolved;
+
+        Ok(())
     }
 }
 
@@ -55,6 +147,12 @@ impl Packable for MDNSResourceRecord {
         let (rr_name, rr_type, cache_flush_rr_class, ttl, rd_length) =
             unpack_chain!(data => MDNSFQDN, MDNSTYPE, u16, u32, u16);
 
+        if data.len() < rd_length as usize * 8 {
+            anyhow::bail!(
+                "Record data of {} bytes runs past the end of the packet.",
+                rd_length
+            );
+        }
         let mut r_data = (
             data.drain(..rd_length as usize * 8)
                 .as_bitslice()
@@ -75,9 +173,9 @@ impl Packable for MDNSResourceRecord {
             MDNSTYPE::KX,
             MDNSTYPE::RP,
             MDNSTYPE::SRV,
-            MDNSTYPE::NSEC,
         ]
         .contains(&rr_type)
+            && rd_length >= 2
         {
             let has_pointer = r_data.0[rd_length as usize - 2] & 0b1100_0000 == 0b1100_0000;
 
@@ -114,3 +212,26 @@ impl Packable for MDNSResourceRecord {
     the rdata of other types should be compressed, names that appear
     within the rdata of any type not listed above MUST NOT be compressed.
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpack_repeated_types() {
+        // The class and rdata length are both `u16`s, and must not be mixed up.
+        let record = MDNSResourceRecord {
+            rr_name: MDNSFQDN::new("printer.local"),
+            rr_type: MDNSTYPE::A,
+            cache_flush_rr_class: 0x8001,
+            ttl: 120,
+            rd_length: 4,
+            r_data: (vec![192, 168, 1, 20], None),
+        };
+
+        let unpacked = MDNSResourceRecord::unpack(&mut record.pack()).unwrap();
+        assert_eq!(unpacked.cache_flush_rr_class, 0x8001);
+        assert_eq!(unpacked.rd_length, 4);
+        assert_eq!(unpacked.ttl, 120);
+    }
+}
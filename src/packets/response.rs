@@ -5,8 +5,12 @@ use anyhow::Result;
 use crate::concat_packable_bits;
 
 use super::{
-    header::MDNSHeader, pack::Packable, query::MDNSQuery, resource_record::MDNSResourceRecord,
-    util::read_vec_of_t, MDNSTYPE,
+    header::{MDNSHeader, FLAG_AA, FLAG_QR},
+    pack::Packable,
+    query::MDNSQuery,
+    resource_record::MDNSResourceRecord,
+    util::read_vec_of_t,
+    MDNSTYPE,
 };
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -26,28 +30,48 @@ impl MDNSResponse {
         mut answers: Vec<MDNSResourceRecord>,
         mut authorities: Vec<MDNSResourceRecord>,
         mut additional: Vec<MDNSResourceRecord>,
-    ) -> Self {
+    ) -> Result<Self> {
         let mut data_cache = HashMap::new();
 
-        queries
-            .iter_mut()
-            .for_each(|q| q.resolve(&data, &mut data_cache));
-        answers
-            .iter_mut()
-            .for_each(|a| a.resolve(&data, &mut data_cache));
-        authorities
-            .iter_mut()
-            .for_each(|a| a.resolve(&data, &mut data_cache));
-        additional
+        for query in &mut queries {
+            query.resolve(&data, &mut data_cache)?;
+        }
+        for record in answers
             .iter_mut()
-            .for_each(|a| a.resolve(&data, &mut data_cache));
+            .chain(&mut authorities)
+            .chain(&mut additional)
+        {
+            record.resolve(&data, &mut data_cache)?;
+        }
 
-        MDNSResponse {
+        Ok(MDNSResponse {
             header,
             queries,
             answers,
             authorities,
             additional,
+        })
+    }
+
+    /// Builds an authoritative response, with the header counts taken from the given sections.
+    pub fn authoritative(
+        answers: Vec<MDNSResourceRecord>,
+        additional: Vec<MDNSResourceRecord>,
+    ) -> Self {
+        let header = MDNSHeader {
+            flags: FLAG_QR | FLAG_AA,
+            questions: 0,
+            answer_rrs: answers.len() as u16,
+            additional_rrs: additional.len() as u16,
+            ..MDNSHeader::new()
+        };
+
+        MDNSResponse {
+            header,
+            queries: vec![],
+            answers,
+            authorities: vec![],
+            additional,
         }
     }
 
@@ -84,9 +108,70 @@ impl Packable for MDNSResponse {
         let authorities = read_vec_of_t(data, header.authority_rrs as usize)?;
         let additional = read_vec_of_t(data, header.additional_rrs as usize)?;
 
-        let response =
-            MDNSResponse::new(data_copy, header, queries, answers, authorities, additional);
+        MDNSResponse::new(data_copy, header, queries, answers, authorities, additional)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitvec::{order::Msb0, view::BitView};
+
+    use super::*;
+    use crate::packets::{fqdn::MDNSFQDN, resource_record::MDNSResourceRecord};
+
+    fn a_response() -> crate::Data {
+        let record = MDNSResourceRecord::new(
+            MDNSFQDN::new("printer.local"),
+            MDNSTYPE::A,
+            true,
+            120,
+            vec![192, 168, 1, 20],
+        );
+        MDNSResponse::authoritative(vec![record], vec![]).pack()
+    }
+
+    #[test]
+    fn test_unpack_unknown_type() {
+        let mut data = a_response();
+        assert!(MDNSResponse::unpack(&mut data.clone()).is_ok());
+
+        // The type follows the header and the 15 byte name.
+        data[(12 + 15) * 8..(12 + 17) * 8].copy_from_bitslice(99u16.to_be_bytes().view_bits());
+        assert!(MDNSResponse::unpack(&mut data).is_err());
+    }
+
+    #[test]
+    fn test_unpack_truncated() {
+        let data = a_response();
+        for len in (0..data.len()).step_by(8) {
+            assert!(MDNSResponse::unpack(&mut data[..len].to_bitvec()).is_err());
+        }
+    }
 
-        Ok(response)
+    #[test]
+    fn test_unpack_pointer_loop() {
+        // A question whose name is a pointer to itself.
+        let bytes: [u8; 18] = [
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0b1100_0000,
+            12,
+            0,
+            12,
+            0,
+            1,
+        ];
+        let mut data = bytes.view_bits::<Msb0>().to_bitvec();
+        assert!(MDNSResponse::unpack(&mut data).is_err());
     }
 }
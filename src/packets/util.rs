@@ -6,45 +6,48 @@ use anyhow::Result;
 use super::pack::Packable;
 
 pub fn read_u16s_be<const N: usize>(data: &mut crate::Data) -> Result<[u16; N]> {
-    Ok([0; N].map(|_| load!(data => u16)))
+    let mut out = [0; N];
+    for value in &mut out {
+        *value = load!(data => u16);
+    }
+    Ok(out)
 }
 
 pub fn read_vec_of_t<T: Packable + Debug>(data: &mut crate::Data, n: usize) -> Result<Vec<T>> {
-    Ok((0..n).map(|_| T::unpack(data).unwrap()).collect::<Vec<_>>())
+    (0..n).map(|_| T::unpack(data)).collect()
 }
 
 /// Concatenate a series of `Packable` types into a single `BitVec`.
 #[macro_export]
 macro_rules! concat_packable_bits {
     ($($j:expr),*) => {{
-        let mut out = crate::Data::new();
+        let mut out = $crate::Data::new();
         $(out.extend($j.pack());)*
         out
     }};
 }
 
 /// Unpack a series of `Packable` types from a `&mut BitVec`.
+///
+/// Tuple operands are evaluated left to right, so the types are unpacked in order, and repeated
+/// types each get their own element.
 #[macro_export]
 macro_rules! unpack_chain {
     ($data:ident => $($t:ty),*) => {{
-        use paste::paste;
-        $(
-            #[allow(non_snake_case)]
-            let paste! {[<_$t>]} = <$t>::unpack($data)?;
-        )*
-
-        ($(paste! {[<_$t>]}),*)
+        ($(<$t>::unpack($data)?),*)
     }};
 }
 
-/// Drain a numerical value from a `&mut BitVec`.
+/// Drain a numerical value from a `&mut BitVec`, returning an error from the enclosing function if
+/// there aren't enough bits left.
 #[macro_export]
 macro_rules! load {
     ($data:expr => $ty:ty) => {{
         use bitvec::field::BitField;
-        $data
-            .drain(..::std::mem::size_of::<$ty>() * 8)
-            .as_bitslice()
-            .load_be::<$ty>()
+        let bits = ::std::mem::size_of::<$ty>() * 8;
+        if $data.len() < bits {
+            ::anyhow::bail!("Expected {} more bits, found {}.", bits, $data.len());
+        }
+        $data.drain(..bits).as_bitslice().load_be::<$ty>()
     }};
 }
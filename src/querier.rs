@@ -0,0 +1,1095 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cache::{CacheEvent, CacheKey, CachedRecord, RecordCache},
+    dns_sd::{
+        Browse, BrowseName, Browser, DomainKind, Resolve, Resolver, ServiceInstanceName,
+        ServiceType, Subtype,
+    },
+    host::{reverse_name, HostLookup, HostQuery, IpFamily, ReverseLookup, ReverseQuery},
+    packets::{
+        fqdn::MDNSFQDN, pack::Packable, packet::MDNSPacket, query::MDNSQuery,
+        resource_record::MDNSResourceRecord, response::MDNSResponse, CLASS_ANY, MDNSTYPE,
+    },
+    transmit::{Transmit, MAX_PACKET_SIZE},
+};
+
+/// Fractions of a record's TTL at which it is re-queried, see RFC 6762 section 5.2.
+const REFRESH_POINTS: [f64; 4] = [0.80, 0.85, 0.90, 0.95];
+/// Random variance added to each refresh point, as a fraction of the TTL.
+const REFRESH_JITTER: f64 = 0.02;
+/// Range of the random delay before the first query of a question, see RFC 6762 section 5.2.
+const INITIAL_DELAY_MS: std::ops::RangeInclusive<u64> = 20..=120;
+const FIRST_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Questions due within this window of each other are sent in the same packet.
+const MERGE_WINDOW: Duration = Duration::from_millis(250);
+/// How soon a question has to be due for another host's identical question to stand in for it.
+const SUPPRESSION_WINDOW: Duration = Duration::from_secs(1);
+
+/// Keeps a question alive in the [`Querier`] until dropped.
+#[derive(Debug)]
+pub struct QueryHandle {
+    _token: Arc<()>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum QuerierEvent {
+    Cache(CacheEvent),
+    /// A maintenance query was sent for `record` at `percent`% of its TTL.
+    Refreshing {
+        record: MDNSResourceRecord,
+        percent: u8,
+    },
+}
+
+#[derive(Debug)]
+struct Interest {
+    name: MDNSFQDN,
+    rr_type: MDNSTYPE,
+    handles: Vec<Weak<()>>,
+    next_send: Instant,
+    interval: Duration,
+}
+
+impl Interest {
+    fn is_alive(&self) -> bool {
+        self.handles.iter().any(|h| h.strong_count() > 0)
+    }
+
+    fn asks(&self, name: &MDNSFQDN, rr_type: MDNSTYPE) -> bool {
+        self.rr_type == rr_type && self.name.eq_ignore_ascii_case(name)
+    }
+
+    fn wants(&self, record: &MDNSResourceRecord) -> bool {
+        (self.rr_type == MDNSTYPE::ANY || self.rr_type == record.rr_type)
+            && self.name.eq_ignore_ascii_case(&record.rr_name)
+    }
+}
+
+/// Where a cached record is in its maintenance schedule.
+#[derive(Debug)]
+struct Refresh {
+    received: Instant,
+    step: usize,
+    at: Option<Instant>,
+}
+
+/// A sans-IO mDNS querier.
+///
+/// Incoming packets are fed in with `handle_*`, time is advanced with [`Querier::handle_timeout`],
+/// and outgoing packets and events are drained with [`Querier::poll_transmit`] and
+/// [`Querier::poll_event`].
+pub struct Querier {
+    cache: RecordCache,
+    interests: Vec<Interest>,
+    refreshes: HashMap<(CacheKey, Vec<u8>), Refresh>,
+    browses: Vec<Browse>,
+    resolves: Vec<Resolve>,
+    host_queries: Vec<HostQuery>,
+    reverse_queries: Vec<ReverseQuery>,
+    interface: Option<u32>,
+    rng: fastrand::Rng,
+    transmits: VecDeque<Transmit>,
+    events: VecDeque<QuerierEvent>,
+}
+
+impl Default for Querier {
+    fn default() -> Self {
+        Querier::with_rng(fastrand::Rng::new())
+    }
+}
+
+impl Querier {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a querier whose random jitter is reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        Querier::with_rng(fastrand::Rng::with_seed(seed))
+    }
+
+    fn with_rng(rng: fastrand::Rng) -> Self {
+        Querier {
+            cache: RecordCache::new(),
+            interests: vec![],
+            refreshes: HashMap::new(),
+            browses: vec![],
+            resolves: vec![],
+            host_queries: vec![],
+            reverse_queries: vec![],
+            interface: None,
+            rng,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn cache(&self) -> &RecordCache {
+        &self.cache
+    }
+
+    /// Sets the index of the interface this querier's packets are sent and received on.
+    pub fn set_interface(&mut self, index: u32) {
+        self.interface = Some(index);
+    }
+
+    /// Asks a question continuously for as long as the handle lives, and keeps the matching
+    /// records fresh.
+    ///
+    /// The first query goes out after a random 20-120ms delay, after which the interval starts
+    /// at one second and doubles up to an hour. Handles asking the same question share a schedule.
+    pub fn query(&mut self, name: &MDNSFQDN, rr_type: MDNSTYPE, now: Instant) -> QueryHandle {
+        let first_send = now + Duration::from_millis(self.rng.u64(INITIAL_DELAY_MS));
+        self.add_interest(name, rr_type, first_send)
+    }
+
+    fn add_interest(
+        &mut self,
+        name: &MDNSFQDN,
+        rr_type: MDNSTYPE,
+        first_send: Instant,
+    ) -> QueryHandle {
+        let token = Arc::new(());
+
+        self.interests.retain(Interest::is_alive);
+        match self.interests.iter_mut().find(|i| i.asks(name, rr_type)) {
+            Some(interest) => interest.handles.push(Arc::downgrade(&token)),
+            None => self.interests.push(Interest {
+                name: name.clone(),
+                rr_type,
+                handles: vec![Arc::downgrade(&token)],
+                next_send: first_send,
+                interval: FIRST_INTERVAL,
+            }),
+        }
+        self.schedule_refreshes();
+
+        QueryHandle { _token: token }
+    }
+
+    /// Browses for instances of `service_type`, such as `_http._tcp`, in the `local` domain.
+    ///
+    /// Each instance is reported once when its PTR record is first cached, including any cached
+    /// already, and once when it is removed by a goodbye or expiry.
+    pub fn browse(&mut self, service_type: &ServiceType, now: Instant) -> Browser {
+        self.browse_ptr(service_type.to_fqdn("local"), now)
+    }
+
+    /// Browses for instances of `service_type` registered under `subtype`, see RFC 6763 section
+    /// 7.1.
+    pub fn browse_subtype(
+        &mut self,
+        subtype: &Subtype,
+        service_type: &ServiceType,
+        now: Instant,
+    ) -> Browser {
+        self.browse_ptr(subtype.to_fqdn(&service_type.to_fqdn("local")), now)
+    }
+
+    /// Browses for the service types being advertised in the `local` domain, see RFC 6763
+    /// section 9.
+    ///
+    /// The events name service types such as `_http._tcp` rather than instances.
+    pub fn browse_service_types(&mut self, now: Instant) -> Browser<ServiceType> {
+        self.browse_ptr(MDNSFQDN::new("_services._dns-sd._udp.local"), now)
+    }
+
+    /// Browses for the domains the local link recommends as `kind`, see RFC 6763 section 11.
+    ///
+    /// The events name domains such as `example.com` rather than instances, which are added and
+    /// removed just as instances are.
+    pub fn browse_domains(&mut self, kind: DomainKind, now: Instant) -> Browser<MDNSFQDN> {
+        self.browse_ptr(kind.to_fqdn("local"), now)
+    }
+
+    fn browse_ptr<T: BrowseName + Send + 'static>(
+        &mut self,
+        service: MDNSFQDN,
+        now: Instant,
+    ) -> Browser<T> {
+        let handle = self.query(&service, MDNSTYPE::PTR, now);
+
+        let (mut browse, events, token) = Browse::new(service.clone());
+        for entry in self.cache.lookup(&service, MDNSTYPE::PTR, CLASS_ANY, now) {
+            browse.observe(&CacheEvent::Added(entry.record.clone()));
+        }
+        self.browses.push(browse);
+
+        Browser::new(handle, events, token)
+    }
+
+    /// Resolves a service instance such as `Printer._http._tcp.local` to its host, port,
+    /// addresses and TXT record, for as long as the resolver lives.
+    ///
+    /// The SRV target's addresses are asked for in turn, unless they already arrived in the
+    /// additional section, and a new [`crate::dns_sd::ResolvedService`] is reported whenever
+    /// anything changes, or [`crate::dns_sd::ResolveEvent::Removed`] once the SRV record is gone.
+    pub fn resolve(&mut self, instance: &ServiceInstanceName, now: Instant) -> Resolver {
+        let handles =
+            [MDNSTYPE::SRV, MDNSTYPE::TXT].map(|ty| self.query(&instance.to_fqdn(), ty, now));
+        let (resolve, updates, token) = Resolve::new(instance.clone());
+        self.resolves.push(resolve);
+        self.update_resolves(now);
+
+        Resolver::new(handles, updates, token)
+    }
+
+    /// Looks up the addresses of a host such as `printer.local`.
+    ///
+    /// The result is reported once every family asked for has been answered, from the cache or
+    /// the network, either with addresses or an NSEC record saying there are none. With a
+    /// `deadline`, whatever has been received by then is reported instead.
+    pub fn resolve_host(
+        &mut self,
+        name: &MDNSFQDN,
+        family: IpFamily,
+        deadline: Option<Instant>,
+        now: Instant,
+    ) -> HostLookup {
+        let handles = family
+            .types()
+            .into_iter()
+            .map(|ty| self.query(name, ty, now))
+            .collect();
+        let (query, lookup) = HostQuery::new(name.clone(), family, deadline, handles);
+        if !query.poll(&self.cache, now) {
+            self.host_queries.push(query);
+        }
+
+        lookup
+    }
+
+    /// Looks up the host names of `address` through its reverse-mapping PTR record, see
+    /// [`crate::host::reverse_name`].
+    ///
+    /// The result is reported as soon as a name is known, or at the `deadline` with none.
+    pub fn resolve_address(
+        &mut self,
+        address: IpAddr,
+        deadline: Option<Instant>,
+        now: Instant,
+    ) -> ReverseLookup {
+        let name = reverse_name(address);
+        let handle = self.query(&name, MDNSTYPE::PTR, now);
+        let (query, lookup) = ReverseQuery::new(name, deadline, handle);
+        if !query.poll(&self.cache, now) {
+            self.reverse_queries.push(query);
+        }
+
+        lookup
+    }
+
+    pub fn handle_response(&mut self, response: &MDNSResponse, now: Instant) {
+        self.cache.insert_response(response, now);
+        self.drain_cache_events();
+        self.update_resolves(now);
+        self.schedule_refreshes();
+    }
+
+    /// Watches another host's query, treating our own identical questions as sent when that
+    /// query's known answers are no more than ours, see RFC 6762 section 7.3.
+    pub fn handle_query(&mut self, query: &MDNSResponse, now: Instant) {
+        // The rest of the known answers are in another packet, so we can't compare them.
+        if query.header.is_truncated() {
+            return;
+        }
+
+        for question in query.queries.iter().filter(|q| !q.unicast_response()) {
+            let ours = self.known_answers(std::slice::from_ref(question), now);
+            let covered = query
+                .answers
+                .iter()
+                .filter(|theirs| answers(question, theirs))
+                .all(|theirs| ours.iter().any(|r| r.same_data(theirs)));
+            if !covered {
+                continue;
+            }
+
+            let planned = now + SUPPRESSION_WINDOW;
+            if let Some(interest) = self
+                .interests
+                .iter_mut()
+                .find(|i| i.asks(&question.qname, question.qtype) && i.next_send <= planned)
+            {
+                interest.next_send = now + interest.interval;
+                interest.interval = (interest.interval * 2).min(MAX_INTERVAL);
+            }
+
+            for entry in self.cache.iter().filter(|e| answers(question, &e.record)) {
+                let key = (CacheKey::of(&entry.record), entry.record.rdata().to_vec());
+                if let Some(refresh) = self.refreshes.get_mut(&key) {
+                    if refresh.at.is_some_and(|at| at <= planned) {
+                        refresh.step += 1;
+                        refresh.at = refresh_at(&mut self.rng, entry, refresh.step);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn handle_timeout(&mut self, now: Instant) {
+        self.cache.expire(now);
+        self.drain_cache_events();
+        // Dropped browsers, resolvers and lookups release their questions here.
+        self.browses.retain(Browse::is_alive);
+        self.update_resolves(now);
+        self.interests.retain(Interest::is_alive);
+        self.schedule_refreshes();
+
+        let mut queries = self.due_interests(now);
+        queries.extend(self.due_refreshes(now));
+        self.send_queries(queries, now);
+    }
+
+    /// The next point in time at which [`Querier::handle_timeout`] should be called.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.refreshes
+            .values()
+            .filter_map(|r| r.at)
+            .chain(self.interests.iter().map(|i| i.next_send))
+            .chain(self.host_queries.iter().filter_map(|q| q.deadline))
+            .chain(self.reverse_queries.iter().filter_map(|q| q.deadline))
+            .chain(self.cache.next_expiry())
+            .min()
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<QuerierEvent> {
+        self.events.pop_front()
+    }
+
+    fn drain_cache_events(&mut self) {
+        while let Some(event) = self.cache.poll_event() {
+            self.browses.retain_mut(|b| b.observe(&event));
+            self.events.push_back(QuerierEvent::Cache(event));
+        }
+    }
+
+    /// Reports changes to resolved services, following their SRV targets' addresses, and
+    /// finishes any host lookups that can be.
+    fn update_resolves(&mut self, now: Instant) {
+        let cache = &self.cache;
+        self.host_queries.retain(|query| !query.poll(cache, now));
+        self.reverse_queries.retain(|query| !query.poll(cache, now));
+
+        let mut resolves = std::mem::take(&mut self.resolves);
+
+        resolves.retain_mut(|resolve| {
+            let current = resolve.current(&self.cache, self.interface, now);
+            let host = current.as_ref().map(|c| c.host.clone());
+            let target = resolve.target.as_ref().map(|(t, _)| t.clone());
+
+            if host != target {
+                resolve.target = host.map(|host| {
+                    // Addresses from the additional section needn't be asked for straight away.
+                    let cached = current.as_ref().is_some_and(|c| !c.addresses.is_empty());
+                    let handles = [MDNSTYPE::A, MDNSTYPE::AAAA]
+                        .into_iter()
+                        .map(|ty| match cached {
+                            true => self.add_interest(&host, ty, now + FIRST_INTERVAL),
+                            false => self.query(&host, ty, now),
+                        })
+                        .collect();
+                    (host, handles)
+                });
+            }
+
+            resolve.update(current)
+        });
+
+        self.resolves = resolves;
+    }
+
+    fn send_queries(&mut self, queries: Vec<MDNSQuery>, now: Instant) {
+        let mut unique: Vec<MDNSQuery> = vec![];
+        for query in queries {
+            if !unique
+                .iter()
+                .any(|q| q.qtype == query.qtype && q.qname.eq_ignore_ascii_case(&query.qname))
+            {
+                unique.push(query);
+            }
+        }
+        let queries = unique;
+
+        if queries.is_empty() {
+            return;
+        }
+
+        let known_answers = self.known_answers(&queries, now);
+
+        // Known answers that don't fit continue in question-less packets, each but the last
+        // flagged as truncated, see RFC 6762 section 7.2.
+        let mut packet = MDNSPacket::with_queries(queries);
+        let mut size = packet.pack().len() / 8;
+        let mut answers = vec![];
+        for record in known_answers {
+            let len = record.pack().len() / 8;
+            if size + len > MAX_PACKET_SIZE && !answers.is_empty() {
+                let mut full = packet.with_known_answers(std::mem::take(&mut answers));
+                full.set_truncated(true);
+                self.transmits.push_back(Transmit::multicast(full.pack()));
+
+                packet = MDNSPacket::with_queries(vec![]);
+                size = packet.pack().len() / 8;
+            }
+            size += len;
+            answers.push(record);
+        }
+
+        let packet = packet.with_known_answers(answers);
+        self.transmits.push_back(Transmit::multicast(packet.pack()));
+    }
+
+    /// Cached answers to `queries` that still have more than half their TTL left, see RFC 6762
+    /// section 7.1.
+    fn known_answers(&self, queries: &[MDNSQuery], now: Instant) -> Vec<MDNSResourceRecord> {
+        let mut known: Vec<MDNSResourceRecord> = vec![];
+
+        for query in queries {
+            for entry in self
+                .cache
+                .lookup(&query.qname, query.qtype, query.class(), now)
+            {
+                let half_ttl = Duration::from_secs(entry.record.ttl as u64) / 2;
+                if entry.remaining_ttl(now) <= half_ttl
+                    || known.iter().any(|k| k.same_data(&entry.record))
+                {
+                    continue;
+                }
+
+                let mut record = entry.record.clone();
+                record.ttl = entry.remaining_ttl(now).as_secs() as u32;
+                known.push(record);
+            }
+        }
+
+        known
+    }
+
+    /// Tracks a refresh schedule for every cached record someone is interested in.
+    fn schedule_refreshes(&mut self) {
+        let Querier {
+            cache,
+            interests,
+            refreshes,
+            rng,
+            ..
+        } = self;
+
+        let wanted = cache
+            .iter()
+            .filter(|e| interests.iter().any(|i| i.wants(&e.record)))
+            .collect::<Vec<_>>();
+
+        refreshes.retain(|(key, rdata), _| {
+            wanted
+                .iter()
+                .any(|e| CacheKey::of(&e.record) == *key && e.record.rdata() == rdata)
+        });
+
+        for entry in wanted {
+            let refresh = refreshes
+                .entry((CacheKey::of(&entry.record), entry.record.rdata().to_vec()))
+                .or_insert(Refresh {
+                    received: entry.received,
+                    step: 0,
+                    at: refresh_at(rng, entry, 0),
+                });
+
+            // The record was received again, so its schedule starts over.
+            if refresh.received != entry.received {
+                *refresh = Refresh {
+                    received: entry.received,
+                    step: 0,
+                    at: refresh_at(rng, entry, 0),
+                };
+            }
+        }
+    }
+
+    /// Collects the questions of every interest that is due, plus any due shortly after so they
+    /// share a packet, and backs off their intervals.
+    fn due_interests(&mut self, now: Instant) -> Vec<MDNSQuery> {
+        if !self.interests.iter().any(|i| i.next_send <= now) {
+            return vec![];
+        }
+
+        self.interests
+            .iter_mut()
+            .filter(|i| i.next_send <= now + MERGE_WINDOW)
+            .map(|interest| {
+                // The first query asks for unicast replies, see RFC 6762 section 5.4.
+                let first = interest.interval == FIRST_INTERVAL;
+                interest.next_send = now + interest.interval;
+                interest.interval = (interest.interval * 2).min(MAX_INTERVAL);
+                MDNSQuery::from_fqdn(interest.name.clone(), interest.rr_type, first)
+            })
+            .collect()
+    }
+
+    fn due_refreshes(&mut self, now: Instant) -> Vec<MDNSQuery> {
+        let mut queries = vec![];
+
+        for entry in self.cache.iter() {
+            let key = (CacheKey::of(&entry.record), entry.record.rdata().to_vec());
+            let Some(refresh) = self.refreshes.get_mut(&key) else {
+                continue;
+            };
+            if refresh.at.is_none_or(|at| at > now) {
+                continue;
+            }
+
+            queries.push(MDNSQuery::from_fqdn(
+                entry.record.rr_name.clone(),
+                entry.record.rr_type,
+                false,
+            ));
+            self.events.push_back(QuerierEvent::Refreshing {
+                record: entry.record.clone(),
+                percent: (REFRESH_POINTS[refresh.step] * 100.0).round() as u8,
+            });
+
+            refresh.step += 1;
+            refresh.at = refresh_at(&mut self.rng, entry, refresh.step);
+        }
+
+        queries
+    }
+}
+
+/// Whether `record` is an answer to `question`.
+fn answers(question: &MDNSQuery, record: &MDNSResourceRecord) -> bool {
+    (question.qtype == MDNSTYPE::ANY || question.qtype == record.rr_type)
+        && question.qname.eq_ignore_ascii_case(&record.rr_name)
+}
+
+fn refresh_at(rng: &mut fastrand::Rng, entry: &CachedRecord, step: usize) -> Option<Instant> {
+    let fraction = REFRESH_POINTS.get(step)? + rng.f64() * REFRESH_JITTER;
+    let at = entry.received + Duration::from_secs_f64(entry.record.ttl as f64 * fraction);
+
+    (at < entry.expires).then_some(at)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitvec::view::BitView;
+
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::{
+        dns_sd::{BrowseEvent, ResolveEvent, ResolvedService},
+        packets::{
+            packet::MDNSPacket,
+            rdata::{NSEC, SRV, TXT},
+        },
+    };
+
+    #[test]
+    fn test_maintenance_queries() {
+        let now = Instant::now();
+        let name = MDNSFQDN::new("_http._tcp.local");
+        let mut querier = Querier::with_seed(0);
+
+        // Keep the continuous query itself out of the way.
+        let handle = querier.query(&name, MDNSTYPE::PTR, now);
+        querier.interests[0].next_send = now + MAX_INTERVAL;
+
+        let record = MDNSResourceRecord::new(name.clone(), MDNSTYPE::PTR, false, 100, vec![1]);
+        querier.handle_response(&MDNSResponse::authoritative(vec![record], vec![]), now);
+
+        let mut percents = vec![];
+        for secs in 1..100 {
+            querier.handle_timeout(now + Duration::from_secs(secs));
+            while let Some(event) = querier.poll_event() {
+                if let QuerierEvent::Refreshing { percent, .. } = event {
+                    assert!(querier.poll_transmit().is_some());
+                    assert!((percent as u64..=percent as u64 + 3).contains(&secs));
+                    percents.push(percent);
+                }
+            }
+        }
+        assert_eq!(percents, [80, 85, 90, 95]);
+
+        // Without an interest, records simply expire.
+        drop(handle);
+        let record = MDNSResourceRecord::new(name, MDNSTYPE::PTR, false, 100, vec![1]);
+        let later = now + Duration::from_secs(200);
+        querier.handle_response(&MDNSResponse::authoritative(vec![record], vec![]), later);
+        querier.handle_timeout(later + Duration::from_secs(90));
+        assert!(querier.poll_transmit().is_none());
+    }
+
+    #[test]
+    fn test_continuous_query_backoff() {
+        let now = Instant::now();
+        let mut querier = Querier::with_seed(0);
+
+        let http = querier.query(&MDNSFQDN::new("_http._tcp.local"), MDNSTYPE::PTR, now);
+        let ipp = querier.query(&MDNSFQDN::new("_ipp._tcp.local"), MDNSTYPE::PTR, now);
+
+        let mut sent = vec![];
+        let mut at = now;
+        while at < now + Duration::from_secs(40) {
+            querier.handle_timeout(at);
+            while let Some(transmit) = querier.poll_transmit() {
+                sent.push((at - now, transmit));
+            }
+            at += Duration::from_millis(10);
+        }
+
+        // Both questions share every packet, with the gaps doubling from one second.
+        let first = sent[0].0;
+        assert!(first >= Duration::from_millis(20) && first <= Duration::from_millis(130));
+        for (i, (_, transmit)) in sent.iter().enumerate() {
+            let mut data = transmit.packet.view_bits().to_bitvec();
+            let packet = MDNSPacket::unpack(&mut data).unwrap();
+            assert_eq!(packet.queries().len(), 2);
+            // Only the first query asks for unicast replies.
+            assert!(packet
+                .queries()
+                .iter()
+                .all(|q| q.unicast_response() == (i == 0)));
+        }
+        let gaps = sent
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(gaps, [1, 2, 4, 8, 16]);
+
+        drop((http, ipp));
+        querier.handle_timeout(now + MAX_INTERVAL * 2);
+        assert!(querier.poll_transmit().is_none());
+    }
+
+    #[test]
+    fn test_known_answers() {
+        let now = Instant::now();
+        let name = MDNSFQDN::new("_http._tcp.local");
+        let mut querier = Querier::with_seed(0);
+
+        let _handle = querier.query(&name, MDNSTYPE::PTR, now);
+        let answers = (0..100)
+            .map(|i| {
+                let instance = format!("Device number {i:03}._http._tcp.local");
+                let rdata = MDNSFQDN::new(&instance).pack().into_vec();
+                MDNSResourceRecord::new(name.clone(), MDNSTYPE::PTR, false, 4500, rdata)
+            })
+            .collect::<Vec<_>>();
+        querier.handle_response(&MDNSResponse::authoritative(answers, vec![]), now);
+
+        // A record past half its TTL is no longer a known answer.
+        let stale = MDNSResourceRecord::new(name.clone(), MDNSTYPE::PTR, false, 10, vec![0]);
+        querier.handle_response(&MDNSResponse::authoritative(vec![stale], vec![]), now);
+
+        querier.handle_timeout(now + Duration::from_secs(6));
+        let packets = std::iter::from_fn(|| querier.poll_transmit())
+            .map(|t| {
+                assert!(t.packet.len() <= MAX_PACKET_SIZE);
+                MDNSPacket::unpack(&mut t.packet.view_bits().to_bitvec()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(packets.len() > 1);
+        assert_eq!(packets[0].queries().len(), 1);
+        assert!(packets[1..].iter().all(|p| p.queries().is_empty()));
+
+        let (last, rest) = packets.split_last().unwrap();
+        assert!(rest.iter().all(|p| p.header().is_truncated()));
+        assert!(!last.header().is_truncated());
+
+        let known = packets.iter().flat_map(|p| p.known_answers());
+        assert_eq!(known.clone().count(), 100);
+        assert!(known.clone().all(|r| r.ttl == 4494));
+    }
+
+    #[test]
+    fn test_known_answers_for_overlapping_questions() {
+        let now = Instant::now();
+        let name = MDNSFQDN::new("_http._tcp.local");
+        let mut querier = Querier::with_seed(0);
+
+        let _ptr = querier.query(&name, MDNSTYPE::PTR, now);
+        let _any = querier.query(&name, MDNSTYPE::ANY, now);
+        let rdata = MDNSFQDN::new("Printer._http._tcp.local").pack().into_vec();
+        let ptr = MDNSResourceRecord::new(name.clone(), MDNSTYPE::PTR, false, 4500, rdata);
+        querier.handle_response(&MDNSResponse::authoritative(vec![ptr], vec![]), now);
+
+        // Both questions are answered by the same record, which is listed once.
+        querier.handle_timeout(now + Duration::from_secs(6));
+        let packet = querier.poll_transmit().unwrap();
+        let packet = MDNSPacket::unpack(&mut packet.packet.view_bits().to_bitvec()).unwrap();
+        assert_eq!(packet.queries().len(), 2);
+        assert_eq!(packet.known_answers().len(), 1);
+    }
+
+    #[test]
+    fn test_browse() {
+        let now = Instant::now();
+        let ptr = |service: &str, instance: &str, ttl| {
+            let rdata = MDNSFQDN::new(instance).pack().into_vec();
+            MDNSResourceRecord::new(MDNSFQDN::new(service), MDNSTYPE::PTR, false, ttl, rdata)
+        };
+        let http = |instance, ttl| ptr("_http._tcp.local", instance, ttl);
+        let response = |records| MDNSResponse::authoritative(records, vec![]);
+        let instance = |name| ServiceInstanceName::try_from(&MDNSFQDN::new(name)).unwrap();
+        let added = |name| Some(BrowseEvent::Added(instance(name)));
+        let removed = |name| Some(BrowseEvent::Removed(instance(name)));
+
+        let mut querier = Querier::with_seed(0);
+        querier.handle_response(&response(vec![http("Cached._http._tcp.local", 120)]), now);
+        let browser = querier.browse(&"_http._tcp".parse().unwrap(), now);
+        assert_eq!(browser.try_recv(), added("Cached._http._tcp.local"));
+
+        // Repeats, whether in the same response, later ones or another case, are reported once.
+        let printer = http("Printer._http._tcp.local", 120);
+        querier.handle_response(&response(vec![printer.clone(), printer.clone()]), now);
+        querier.handle_response(&response(vec![http("PRINTER._http._tcp.local", 60)]), now);
+        querier.handle_response(&response(vec![ptr("_ipp._tcp.local", "Other", 120)]), now);
+        assert_eq!(browser.try_recv(), added("Printer._http._tcp.local"));
+        assert_eq!(browser.try_recv(), None);
+
+        // Names that aren't valid service instance names aren't reported.
+        querier.handle_response(&response(vec![http("printer.local", 120)]), now);
+        assert_eq!(browser.try_recv(), None);
+
+        // Goodbyes and expiry remove instances, once no record names them.
+        querier.handle_response(&response(vec![http("Printer._http._tcp.local", 0)]), now);
+        querier.handle_timeout(now + Duration::from_secs(2));
+        assert_eq!(browser.try_recv(), None);
+        querier.handle_timeout(now + Duration::from_secs(60));
+        assert_eq!(browser.try_recv(), removed("PRINTER._http._tcp.local"));
+        querier.handle_timeout(now + Duration::from_secs(120));
+        assert_eq!(browser.try_recv(), removed("Cached._http._tcp.local"));
+
+        // A dropped browser goes away even with nothing more to report.
+        drop(browser);
+        querier.handle_timeout(now + Duration::from_secs(120));
+        assert!(querier.browses.is_empty());
+        assert!(querier.interests.is_empty());
+    }
+
+    #[test]
+    fn test_browse_subtype() {
+        let now = Instant::now();
+        let printer = Subtype::new("_printer").unwrap();
+        let name = printer.to_fqdn(&MDNSFQDN::new("_http._tcp.local"));
+        let ptr = |name: &MDNSFQDN, instance: &str| {
+            let rdata = MDNSFQDN::new(instance).pack().into_vec();
+            MDNSResourceRecord::new(name.clone(), MDNSTYPE::PTR, false, 120, rdata)
+        };
+
+        let mut querier = Querier::with_seed(0);
+        let browser = querier.browse_subtype(&printer, &"_http._tcp".parse().unwrap(), now);
+        let records = vec![
+            ptr(&name, "Printer._http._tcp.local"),
+            ptr(
+                &MDNSFQDN::new("_http._tcp.local"),
+                "Camera._http._tcp.local",
+            ),
+        ];
+        querier.handle_response(&MDNSResponse::authoritative(records, vec![]), now);
+
+        // Only instances registered under the subtype are reported.
+        let events = std::iter::from_fn(|| browser.try_recv()).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [BrowseEvent::Added(
+                ServiceInstanceName::try_from(&MDNSFQDN::new("Printer._http._tcp.local")).unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_browse_service_types() {
+        let now = Instant::now();
+        let meta = MDNSFQDN::new("_services._dns-sd._udp.local");
+        let ptr = |service: &str, ttl| {
+            let rdata = MDNSFQDN::new(service).pack().into_vec();
+            MDNSResourceRecord::new(meta.clone(), MDNSTYPE::PTR, false, ttl, rdata)
+        };
+        let response = |records| MDNSResponse::authoritative(records, vec![]);
+
+        let mut querier = Querier::with_seed(0);
+        let browser = querier.browse_service_types(now);
+        querier.handle_timeout(now + Duration::from_millis(200));
+        let packet = querier.poll_transmit().unwrap();
+        let packet = MDNSPacket::unpack(&mut packet.packet.view_bits().to_bitvec()).unwrap();
+        assert_eq!(packet.queries()[0].qname, meta);
+
+        // Two hosts advertising the same type make it appear once.
+        querier.handle_response(&response(vec![ptr("_http._tcp.local", 120)]), now);
+        querier.handle_response(&response(vec![ptr("_http._tcp.local", 120)]), now);
+        querier.handle_response(&response(vec![ptr("_ipp._tcp.local", 10)]), now);
+        let types = std::iter::from_fn(|| browser.try_recv()).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                BrowseEvent::Added("_http._tcp".parse().unwrap()),
+                BrowseEvent::Added("_ipp._tcp".parse().unwrap()),
+            ]
+        );
+
+        querier.handle_timeout(now + Duration::from_secs(10));
+        assert_eq!(
+            browser.try_recv(),
+            Some(BrowseEvent::Removed("_ipp._tcp".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_browse_domains() {
+        let now = Instant::now();
+        let domain = MDNSFQDN::new("example.com");
+
+        let mut querier = Querier::with_seed(0);
+        let browser = querier.browse_domains(DomainKind::Browse, now);
+        querier.handle_timeout(now + Duration::from_millis(200));
+        let packet = querier.poll_transmit().unwrap();
+        let query = MDNSResponse::unpack(&mut packet.packet.view_bits().to_bitvec()).unwrap();
+        assert_eq!(
+            query.queries[0].qname,
+            MDNSFQDN::new("b._dns-sd._udp.local")
+        );
+
+        // Recommended domains come and go like instances do.
+        let mut ptr = DomainKind::Browse.record(&domain).record;
+        querier.handle_response(&MDNSResponse::authoritative(vec![ptr.clone()], vec![]), now);
+        assert_eq!(browser.try_recv(), Some(BrowseEvent::Added(domain.clone())));
+
+        ptr.ttl = 0;
+        querier.handle_response(&MDNSResponse::authoritative(vec![ptr], vec![]), now);
+        querier.handle_timeout(now + Duration::from_secs(1));
+        assert_eq!(browser.try_recv(), Some(BrowseEvent::Removed(domain)));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let now = Instant::now();
+        let instance =
+            ServiceInstanceName::new("Printer", "_http._tcp".parse().unwrap(), "local").unwrap();
+        let host = MDNSFQDN::new("printer.local");
+        let srv = |port| {
+            let rdata = SRV {
+                priority: 0,
+                weight: 0,
+                port,
+                target: host.clone(),
+            };
+            MDNSResourceRecord::new(
+                instance.to_fqdn(),
+                MDNSTYPE::SRV,
+                true,
+                120,
+                rdata.pack().into_vec(),
+            )
+        };
+        let txt = TXT::from_pairs([("path", Some(&b"/"[..]))]).unwrap();
+        let txt_record = MDNSResourceRecord::new(
+            instance.to_fqdn(),
+            MDNSTYPE::TXT,
+            true,
+            4500,
+            txt.pack().into_vec(),
+        );
+        let a =
+            MDNSResourceRecord::new(host.clone(), MDNSTYPE::A, true, 120, vec![192, 168, 1, 20]);
+
+        let mut querier = Querier::with_seed(0);
+        querier.set_interface(3);
+        let resolver = querier.resolve(&instance, now);
+        assert_eq!(resolver.try_recv(), None);
+        let resolved = |resolver: &Resolver| match resolver.try_recv() {
+            Some(ResolveEvent::Resolved(resolved)) => resolved,
+            event => panic!("expected a resolved service, got {event:?}"),
+        };
+
+        let response = MDNSResponse::authoritative(vec![srv(80), txt_record], vec![a]);
+        querier.handle_response(&response, now);
+        assert_eq!(
+            resolved(&resolver),
+            ResolvedService {
+                instance: instance.clone(),
+                host: host.clone(),
+                port: 80,
+                addresses: vec![Ipv4Addr::new(192, 168, 1, 20).into()],
+                txt,
+                interface: Some(3),
+            }
+        );
+
+        // The address came in the additional section, so only the SRV and TXT are asked for.
+        querier.handle_timeout(now + Duration::from_millis(200));
+        let questions = std::iter::from_fn(|| querier.poll_transmit())
+            .flat_map(|t| {
+                let packet = MDNSPacket::unpack(&mut t.packet.view_bits().to_bitvec()).unwrap();
+                packet.queries().iter().map(|q| q.qtype).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(questions, [MDNSTYPE::SRV, MDNSTYPE::TXT]);
+
+        // Changes are reported as they arrive.
+        let later = now + Duration::from_secs(2);
+        let aaaa = MDNSResourceRecord::new(host.clone(), MDNSTYPE::AAAA, true, 120, vec![0xfe; 16]);
+        querier.handle_response(&MDNSResponse::authoritative(vec![aaaa], vec![]), later);
+        assert_eq!(resolved(&resolver).addresses.len(), 2);
+
+        querier.handle_response(&MDNSResponse::authoritative(vec![srv(8080)], vec![]), later);
+        assert_eq!(resolved(&resolver).port, 8080);
+        assert_eq!(resolver.try_recv(), None);
+
+        // A goodbye for the SRV record is reported.
+        let goodbye = MDNSResourceRecord {
+            ttl: 0,
+            ..srv(8080)
+        };
+        querier.handle_response(&MDNSResponse::authoritative(vec![goodbye], vec![]), later);
+        querier.handle_timeout(later + Duration::from_secs(2));
+        assert_eq!(resolver.try_recv(), Some(ResolveEvent::Removed));
+
+        // Dropping the resolver stops every question it asked, including for the target.
+        let later = later + Duration::from_secs(5);
+        querier.handle_response(&MDNSResponse::authoritative(vec![srv(80)], vec![]), later);
+        assert_eq!(resolved(&resolver).port, 80);
+        drop(resolver);
+        querier.handle_timeout(later);
+        assert!(querier.resolves.is_empty());
+        assert!(querier.interests.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_host() {
+        let now = Instant::now();
+        let host = MDNSFQDN::new("printer.local");
+        let a =
+            MDNSResourceRecord::new(host.clone(), MDNSTYPE::A, true, 120, vec![192, 168, 1, 20]);
+
+        // Already cached addresses are returned straight away.
+        let mut querier = Querier::with_seed(0);
+        querier.handle_response(&MDNSResponse::authoritative(vec![a.clone()], vec![]), now);
+        let lookup = querier.resolve_host(&host, IpFamily::V4, None, now);
+        assert_eq!(
+            lookup.try_recv(),
+            Some(vec![Ipv4Addr::new(192, 168, 1, 20).into()])
+        );
+
+        // Without any IPv6 address, the lookup waits until the deadline.
+        let deadline = now + Duration::from_secs(3);
+        let lookup = querier.resolve_host(&host, IpFamily::Both, Some(deadline), now);
+        assert_eq!(lookup.try_recv(), None);
+        assert!(querier.poll_timeout().is_some_and(|t| t <= deadline));
+        querier.handle_timeout(deadline);
+        assert_eq!(lookup.try_recv().unwrap().len(), 1);
+
+        // Unless an NSEC record, compressed as responders send it, says there is none.
+        let lookup = querier.resolve_host(&host, IpFamily::Both, None, now);
+        #[rustfmt::skip]
+        let packet: Vec<u8> = [
+            &[0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0][..],
+            &[7, b'p', b'r', b'i', b'n', b't', b'e', b'r', 5, b'l', b'o', b'c', b'a', b'l', 0],
+            &[0, 47, 0x80, 1, 0, 0, 0, 120, 0, 10],
+            &[0xc0, 12, 0, 6, 0x40, 0, 0, 0, 0, 1],
+        ]
+        .concat();
+        let response = MDNSResponse::unpack(&mut packet.view_bits().to_bitvec()).unwrap();
+        let nsec = response.answers[0].rdata_as::<NSEC>().unwrap();
+        assert_eq!(nsec.next, host);
+        assert_eq!(nsec.types, [MDNSTYPE::A as u16, MDNSTYPE::NSEC as u16]);
+
+        querier.handle_response(&response, now);
+        assert_eq!(lookup.try_recv().unwrap().len(), 1);
+        assert!(querier.host_queries.is_empty());
+
+        // A dropped lookup stops asking, deadline or not.
+        let mut querier = Querier::with_seed(0);
+        let lookup = querier.resolve_host(&host, IpFamily::Both, Some(deadline), now);
+        assert!(querier.poll_timeout().is_some());
+        drop(lookup);
+        querier.handle_timeout(now);
+        assert!(querier.host_queries.is_empty());
+        assert_eq!(querier.poll_timeout(), None);
+    }
+
+    #[test]
+    fn test_resolve_address() {
+        let now = Instant::now();
+        let address = IpAddr::from([192, 168, 1, 20]);
+        let mut querier = Querier::with_seed(0);
+        let lookup = querier.resolve_address(address, None, now);
+
+        querier.handle_timeout(now + Duration::from_millis(200));
+        let packet = querier.poll_transmit().unwrap();
+        let packet = MDNSPacket::unpack(&mut packet.packet.view_bits().to_bitvec()).unwrap();
+        assert_eq!(
+            packet.queries()[0].qname,
+            MDNSFQDN::new("20.1.168.192.in-addr.arpa")
+        );
+        assert_eq!(packet.queries()[0].qtype, MDNSTYPE::PTR);
+
+        let host = MDNSFQDN::new("printer.local");
+        let ptr = MDNSResourceRecord::new(
+            reverse_name(address),
+            MDNSTYPE::PTR,
+            true,
+            120,
+            host.pack().into_vec(),
+        );
+        querier.handle_response(&MDNSResponse::authoritative(vec![ptr], vec![]), now);
+        assert_eq!(lookup.try_recv(), Some(vec![host]));
+
+        // Nobody answering for an address gives nothing at the deadline.
+        let deadline = now + Duration::from_secs(1);
+        let lookup = querier.resolve_address(IpAddr::from([10, 0, 0, 1]), Some(deadline), now);
+        querier.handle_timeout(deadline);
+        assert_eq!(lookup.try_recv(), Some(vec![]));
+
+        // A dropped lookup stops asking, deadline or not.
+        let mut querier = Querier::with_seed(0);
+        let lookup = querier.resolve_address(address, Some(deadline), now);
+        assert!(querier.poll_timeout().is_some());
+        drop(lookup);
+        querier.handle_timeout(now);
+        assert!(querier.reverse_queries.is_empty());
+        assert_eq!(querier.poll_timeout(), None);
+    }
+
+    #[test]
+    fn test_duplicate_question_suppression() {
+        let now = Instant::now();
+        let name = MDNSFQDN::new("_http._tcp.local");
+        let mut querier = Querier::with_seed(0);
+        let _handle = querier.query(&name, MDNSTYPE::PTR, now);
+
+        let mut other = MDNSResponse::authoritative(vec![], vec![]);
+        other.header.flags = 0;
+        other.header.questions = 1;
+        other.queries = vec![MDNSQuery::from_fqdn(name.clone(), MDNSTYPE::PTR, false)];
+
+        // Another host asking with a known answer we don't have doesn't stand in for us.
+        let mut theirs = other.clone();
+        let known = MDNSResourceRecord::new(name.clone(), MDNSTYPE::PTR, false, 120, vec![1]);
+        theirs.header.answer_rrs = 1;
+        theirs.answers = vec![known];
+        querier.handle_query(&theirs, now);
+        querier.handle_timeout(now + Duration::from_millis(150));
+        assert!(querier.poll_transmit().is_some());
+
+        // Whereas the same question without known answers does.
+        querier.handle_query(&other, now + Duration::from_millis(1100));
+        querier.handle_timeout(now + Duration::from_millis(1200));
+        assert!(querier.poll_transmit().is_none());
+        querier.handle_timeout(now + Duration::from_millis(3150));
+        assert!(querier.poll_transmit().is_some());
+    }
+}
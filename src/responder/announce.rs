@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use crate::packets::resource_record::MDNSResourceRecord;
+
+/* https://www.rfc-editor.org/rfc/rfc6762.html#section-8.3
+    The Multicast DNS responder MUST send at least two unsolicited responses, one second
+    apart. To provide increased robustness against packet loss, a responder MAY send up
+    to eight unsolicited responses, provided that the interval between unsolicited
+    responses increases by at least a factor of two with every response sent.
+*/
+pub const ANNOUNCE_COUNT: u8 = 3;
+pub const FIRST_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait after sending announcement number `sent`.
+pub fn announce_interval(sent: u8) -> Duration {
+    FIRST_ANNOUNCE_INTERVAL * 2u32.pow(sent.saturating_sub(1) as u32)
+}
+
+/// The records as sent in a goodbye packet, with a TTL of zero, see RFC 6762 section 10.1.
+pub fn goodbyes<'a>(
+    records: impl IntoIterator<Item = &'a MDNSResourceRecord>,
+) -> Vec<MDNSResourceRecord> {
+    records
+        .into_iter()
+        .map(|record| MDNSResourceRecord {
+            ttl: 0,
+            ..record.clone()
+        })
+        .collect()
+}
@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use crate::packets::{resource_record::MDNSResourceRecord, response::MDNSResponse};
+
+/* https://www.rfc-editor.org/rfc/rfc6762.html#section-6.3
+    ... a Multicast DNS responder MAY delay its response by up to 500 ms in order to
+    aggregate multiple answers into a single response packet.
+*/
+const AGGREGATION_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PendingAnswer {
+    pub record: MDNSResourceRecord,
+    pub due: Instant,
+    /// When the answer was first due, which aggregation may delay it by no more than 500ms.
+    pub first_due: Instant,
+}
+
+/// Answers waiting out their response delay before being sent.
+#[derive(Debug, Default)]
+pub struct PendingAnswers {
+    answers: Vec<PendingAnswer>,
+}
+
+impl PendingAnswers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queues `record` to be sent at `due`, or earlier if it is already queued for earlier.
+    ///
+    /// Answers due within 500ms of `due` are coalesced with it, going out in the same packet at
+    /// the later of the two times, as long as that is within 500ms of when each was first due.
+    pub fn schedule(&mut self, record: MDNSResourceRecord, due: Instant) {
+        let near = |d: Instant| d.max(due) - d.min(due) <= AGGREGATION_WINDOW;
+        let latest = self
+            .answers
+            .iter()
+            .map(|a| a.due)
+            .filter(|d| near(*d))
+            .max()
+            .unwrap_or(due)
+            .max(due);
+        self.answers
+            .iter_mut()
+            .filter(|a| near(a.due))
+            .for_each(|a| a.due = a.due.max(latest.min(a.first_due + AGGREGATION_WINDOW)));
+
+        let due = latest.min(due + AGGREGATION_WINDOW);
+        match self
+            .answers
+            .iter_mut()
+            .find(|a| a.record.same_data(&record))
+        {
+            Some(pending) => pending.due = pending.due.min(due),
+            None => self.answers.push(PendingAnswer {
+                record,
+                due,
+                first_due: due,
+            }),
+        }
+    }
+
+    /// Treats answers another host just sent as sent by us, see RFC 6762 section 7.4.
+    pub fn observe_response(&mut self, response: &MDNSResponse) {
+        self.answers.retain(|pending| {
+            !response
+                .answers
+                .iter()
+                .any(|theirs| theirs.same_data(&pending.record) && theirs.ttl >= pending.record.ttl)
+        });
+    }
+
+    /// Removes and returns every answer due by `now`.
+    pub fn poll_due(&mut self, now: Instant) -> Vec<MDNSResourceRecord> {
+        let (due, pending) = std::mem::take(&mut self.answers)
+            .into_iter()
+            .partition(|a| a.due <= now);
+        self.answers = pending;
+
+        due.into_iter().map(|a| a.record).collect()
+    }
+
+    pub fn next_due(&self) -> Option<Instant> {
+        self.answers.iter().map(|a| a.due).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::packets::{fqdn::MDNSFQDN, pack::Packable, MDNSTYPE};
+
+    #[test]
+    fn test_duplicate_answer_suppression() {
+        let now = Instant::now();
+        let ptr = |ttl| {
+            let rdata = MDNSFQDN::new("Printer._http._tcp.local").pack().into_vec();
+            let name = MDNSFQDN::new("_http._tcp.local");
+            MDNSResourceRecord::new(name, MDNSTYPE::PTR, false, ttl, rdata)
+        };
+        let response = |ttl| MDNSResponse::authoritative(vec![ptr(ttl)], vec![]);
+
+        let mut pending = PendingAnswers::new();
+        pending.schedule(ptr(4500), now + Duration::from_millis(100));
+
+        // An answer with less of its TTL left doesn't stand in for ours.
+        pending.observe_response(&response(2000));
+        assert!(pending.next_due().is_some());
+
+        pending.observe_response(&response(4500));
+        assert_eq!(pending.next_due(), None);
+        assert!(pending.poll_due(now + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_aggregation_is_bounded() {
+        let now = Instant::now();
+        let ptr = |i: u32| {
+            let rdata = MDNSFQDN::new(&format!("Printer {i}._http._tcp.local"));
+            let name = MDNSFQDN::new("_http._tcp.local");
+            MDNSResourceRecord::new(name, MDNSTYPE::PTR, false, 4500, rdata.pack().into_vec())
+        };
+
+        // A new answer every 400ms, each delayed 450ms, keeps landing near the previous ones.
+        let mut pending = PendingAnswers::new();
+        let mut sent = None;
+        for tick in 0..500 {
+            let at = now + Duration::from_millis(tick * 10);
+            if tick % 40 == 0 {
+                pending.schedule(ptr(tick as u32), at + Duration::from_millis(450));
+            }
+            if pending.poll_due(at).contains(&ptr(0)) {
+                sent = Some(at);
+                break;
+            }
+        }
+
+        let sent = sent.expect("the first answer was never sent");
+        assert!(sent <= now + Duration::from_millis(450) + AGGREGATION_WINDOW);
+    }
+}
@@ -0,0 +1,1982 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use self::announce::{announce_interval, goodbyes, ANNOUNCE_COUNT};
+use self::answers::PendingAnswers;
+use self::probe::{
+    conflicts, probe_packet, tiebreak, INITIAL_DELAY_MS, PROBE_COUNT, PROBE_INTERVAL,
+    TIEBREAK_DELAY,
+};
+use self::rename::{next_name, rename_records};
+use crate::{
+    cache::CacheKey,
+    dns_sd::{DomainKind, ServiceInfo},
+    host::{address, interface_addresses, reverse_name, HostInfo},
+    packets::{
+        fqdn::MDNSFQDN, pack::Packable, query::MDNSQuery, rdata::SRV,
+        resource_record::MDNSResourceRecord, response::MDNSResponse, CLASS_ANY, CLASS_TOP_BIT,
+        MDNSTYPE,
+    },
+    transmit::{Destination, Transmit, MAX_PACKET_SIZE, MDNS_PORT},
+};
+
+mod announce;
+mod answers;
+mod probe;
+mod rename;
+
+/// Range of the random delay before answering with shared records, see RFC 6762 section 6.
+const SHARED_DELAY_MS: std::ops::RangeInclusive<u64> = 20..=120;
+
+/* https://www.rfc-editor.org/rfc/rfc6762.html#section-8.1
+    If fifteen conflicts occur within any ten-second period, then the host MUST wait
+    at least five seconds before each successive additional probe attempt.
+*/
+const CONFLICT_LIMIT: usize = 15;
+const CONFLICT_WINDOW: Duration = Duration::from_secs(10);
+const RATE_LIMITED_DELAY: Duration = Duration::from_secs(5);
+/* https://www.rfc-editor.org/rfc/rfc6762.html#section-6
+    A Multicast DNS responder MUST NOT multicast a record on a given interface until at
+    least one second has elapsed since the last time that record was multicast on that
+    particular interface. ... The one exception is that a Multicast DNS responder MUST
+    respond quickly (within 250 ms) to probe queries ...
+*/
+const MULTICAST_INTERVAL: Duration = Duration::from_secs(1);
+const PROBE_MULTICAST_INTERVAL: Duration = Duration::from_millis(250);
+/// Range of the delay spent waiting for the rest of a truncated query, see RFC 6762 section 7.2.
+const TRUNCATED_DELAY_MS: std::ops::RangeInclusive<u64> = 400..=500;
+/// The TTL cap for replies to legacy unicast queries, see RFC 6762 section 6.7.
+const LEGACY_MAX_TTL: u32 = 10;
+
+/// Identifies a set of records registered together with a [`Responder`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct RegistrationId(u64);
+
+/// Keeps a registration, such as a service, host or recommended domain, alive until dropped,
+/// after which the responder sends goodbyes for it on its next [`Responder::handle_timeout`].
+#[derive(Debug)]
+pub struct RegistrationHandle {
+    pub id: RegistrationId,
+    _token: Arc<()>,
+}
+
+/// A record we are authoritative for.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AuthoritativeRecord {
+    pub record: MDNSResourceRecord,
+    /// Whether the name, type and class are unique to this host, rather than shared.
+    pub unique: bool,
+}
+
+impl AuthoritativeRecord {
+    pub fn shared(record: MDNSResourceRecord) -> Self {
+        AuthoritativeRecord {
+            record,
+            unique: false,
+        }
+    }
+
+    pub fn unique(record: MDNSResourceRecord) -> Self {
+        AuthoritativeRecord {
+            record,
+            unique: true,
+        }
+    }
+
+    /// The record as multicast in a response, with the cache-flush bit set if it is unique.
+    fn answer(&self) -> MDNSResourceRecord {
+        let mut record = self.record.clone();
+        if self.unique {
+            record.cache_flush_rr_class |= CLASS_TOP_BIT;
+        }
+        record
+    }
+
+    fn answers(&self, question: &MDNSQuery) -> bool {
+        (question.qtype == MDNSTYPE::ANY || question.qtype == self.record.rr_type)
+            && (question.class() == CLASS_ANY || question.class() == self.record.class())
+            && question.qname.eq_ignore_ascii_case(&self.record.rr_name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ResponderEvent {
+    /// Probing found nobody else using the unique records, which are now being answered for.
+    Registered(RegistrationId),
+    /// Another host answered with data contradicting one of the unique records after they were
+    /// registered, so they are being probed for again.
+    Conflict(RegistrationId),
+    /// Another host defended one of the unique records' names, so the registration is probing
+    /// again under a new name.
+    Renamed {
+        id: RegistrationId,
+        from: MDNSFQDN,
+        to: MDNSFQDN,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum State {
+    /// Waiting to send probe `sent + 1`, or to finish probing once all have been sent.
+    Probing {
+        sent: u8,
+        next: Instant,
+    },
+    /// Answering, and waiting to send announcement `sent + 1`.
+    Announcing {
+        sent: u8,
+        next: Instant,
+    },
+    Established,
+}
+
+impl State {
+    fn is_probing(&self) -> bool {
+        matches!(self, State::Probing { .. })
+    }
+
+    fn next(&self) -> Option<Instant> {
+        match *self {
+            State::Probing { next, .. } | State::Announcing { next, .. } => Some(next),
+            State::Established => None,
+        }
+    }
+}
+
+/// A query whose known answers continue in further packets from the same source.
+#[derive(Debug)]
+struct TruncatedQuery {
+    query: MDNSResponse,
+    due: Instant,
+}
+
+#[derive(Debug)]
+struct Registration {
+    id: RegistrationId,
+    records: Vec<AuthoritativeRecord>,
+    state: State,
+    /// Dropped along with the handle that unregisters the records.
+    owner: Option<Weak<()>>,
+}
+
+impl Registration {
+    fn unique_records(&self) -> Vec<MDNSResourceRecord> {
+        self.records
+            .iter()
+            .filter(|r| r.unique)
+            .map(|r| r.record.clone())
+            .collect()
+    }
+
+    /// Whether the handle that kept the registration alive has been dropped.
+    fn is_dropped(&self) -> bool {
+        self.owner.as_ref().is_some_and(|o| o.strong_count() == 0)
+    }
+}
+
+/// A sans-IO mDNS responder, answering questions about the records registered with it.
+///
+/// Driven the same way as the [`crate::querier::Querier`].
+pub struct Responder {
+    registrations: Vec<Registration>,
+    next_id: u64,
+    pending: PendingAnswers,
+    rng: fastrand::Rng,
+    transmits: VecDeque<Transmit>,
+    events: VecDeque<ResponderEvent>,
+    /// When recent conflicts happened, for rate limiting.
+    conflicts: VecDeque<Instant>,
+    /// When each of our records was last multicast.
+    multicast_at: HashMap<(CacheKey, Vec<u8>), Instant>,
+    truncated: HashMap<SocketAddr, TruncatedQuery>,
+    /// The latest time we were driven at, when registrations whose handles were dropped are due
+    /// to be unregistered.
+    latest: Option<Instant>,
+}
+
+impl Default for Responder {
+    fn default() -> Self {
+        Responder::with_rng(fastrand::Rng::new())
+    }
+}
+
+impl Responder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a responder whose random delays are reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        Responder::with_rng(fastrand::Rng::with_seed(seed))
+    }
+
+    fn with_rng(rng: fastrand::Rng) -> Self {
+        Responder {
+            registrations: vec![],
+            next_id: 0,
+            pending: PendingAnswers::new(),
+            rng,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+            conflicts: VecDeque::new(),
+            multicast_at: HashMap::new(),
+            truncated: HashMap::new(),
+            latest: None,
+        }
+    }
+
+    /// Starts answering for and announcing `records`.
+    ///
+    /// If any of them are unique they are probed for first, and only answered for once
+    /// [`ResponderEvent::Registered`] is reported.
+    ///
+    /// Unique address records are published along with the PTR records mapping their addresses
+    /// back to the host name, see [`crate::host::reverse_name`].
+    pub fn register(&mut self, records: Vec<AuthoritativeRecord>, now: Instant) -> RegistrationId {
+        self.advance(now);
+        let records = with_reverse_records(records);
+        let id = RegistrationId(self.next_id);
+        self.next_id += 1;
+
+        let state = if records.iter().any(|r| r.unique) {
+            State::Probing {
+                sent: 0,
+                next: now + Duration::from_millis(self.rng.u64(INITIAL_DELAY_MS)),
+            }
+        } else {
+            State::Announcing { sent: 0, next: now }
+        };
+        self.registrations.push(Registration {
+            id,
+            records,
+            state,
+            owner: None,
+        });
+
+        id
+    }
+
+    /// Registers every record advertising `service`, see [`ServiceInfo::records`], until the
+    /// returned handle is dropped.
+    pub fn register_service(&mut self, service: &ServiceInfo, now: Instant) -> RegistrationHandle {
+        self.register_owned(service.records(), now)
+    }
+
+    /// Recommends `domain` as `kind` to the local link, see RFC 6763 section 11.
+    pub fn publish_domain(
+        &mut self,
+        kind: DomainKind,
+        domain: &MDNSFQDN,
+        now: Instant,
+    ) -> RegistrationHandle {
+        self.register_owned(vec![kind.record(domain)], now)
+    }
+
+    /// Publishes `host`'s address records, and its `HINFO` if any, until the handle is dropped.
+    ///
+    /// The records are a snapshot, see [`Responder::refresh_host`] for keeping them current.
+    pub fn publish_host(&mut self, host: &HostInfo, now: Instant) -> RegistrationHandle {
+        self.register_owned(host.records(), now)
+    }
+
+    /// Registers `records` until the returned handle is dropped.
+    fn register_owned(
+        &mut self,
+        records: Vec<AuthoritativeRecord>,
+        now: Instant,
+    ) -> RegistrationHandle {
+        let id = self.register(records, now);
+        let token = Arc::new(());
+        if let Some(registration) = self.registrations.iter_mut().find(|r| r.id == id) {
+            registration.owner = Some(Arc::downgrade(&token));
+        }
+
+        RegistrationHandle { id, _token: token }
+    }
+
+    /// Replaces the records published for `handle` with those of `host`, such as after its name
+    /// or `HINFO` changed.
+    ///
+    /// Keep `host.name` in step with any [`ResponderEvent::Renamed`] for the handle.
+    pub fn update_host(&mut self, handle: &RegistrationHandle, host: &HostInfo, now: Instant) {
+        self.update(handle.id, host.records(), now);
+    }
+
+    /// Re-reads the machine's addresses, see [`crate::host::interface_addresses`], and if they
+    /// changed updates `host` and the records published for `handle` to match. Returns whether
+    /// they changed.
+    ///
+    /// Nothing watches the interfaces, so call this periodically or on a network change.
+    pub fn refresh_host(
+        &mut self,
+        handle: &RegistrationHandle,
+        host: &mut HostInfo,
+        now: Instant,
+    ) -> Result<bool> {
+        let addresses = interface_addresses()?;
+        Ok(self.update_host_addresses(handle, host, addresses, now))
+    }
+
+    /// Like [`Responder::refresh_host`], with the addresses given rather than read.
+    pub fn update_host_addresses(
+        &mut self,
+        handle: &RegistrationHandle,
+        host: &mut HostInfo,
+        addresses: Vec<IpAddr>,
+        now: Instant,
+    ) -> bool {
+        if !host.set_addresses(addresses) {
+            return false;
+        }
+
+        self.update_host(handle, host, now);
+        true
+    }
+
+    /// Replaces the records registered under `id`, sending goodbyes for those no longer present
+    /// and announcing the rest again.
+    ///
+    /// The names are assumed to still be ours, so nothing is probed for again. A registration
+    /// still probing has announced nothing to say goodbye to, and starts probing over instead so
+    /// that the new records are probed for as often as the old ones.
+    pub fn update(&mut self, id: RegistrationId, records: Vec<AuthoritativeRecord>, now: Instant) {
+        self.advance(now);
+        let records = with_reverse_records(records);
+        let Some(index) = self.registrations.iter().position(|r| r.id == id) else {
+            return;
+        };
+        if self.registrations[index].records == records {
+            return;
+        }
+
+        let old = std::mem::replace(&mut self.registrations[index].records, records);
+        if let State::Probing { next, .. } = self.registrations[index].state {
+            self.registrations[index].state = State::Probing { sent: 0, next };
+            return;
+        }
+        let released = old
+            .into_iter()
+            .map(|r| r.record)
+            .filter(|record| !self.holds(record))
+            .collect::<Vec<_>>();
+
+        /* https://www.rfc-editor.org/rfc/rfc6762.html#section-8.4
+            At any time, if the rdata of any of a host's Multicast DNS records
+            changes, the host MUST repeat the Announcing step described above to
+            update neighboring caches.
+        */
+        self.registrations[index].state = State::Announcing { sent: 0, next: now };
+        if !released.is_empty() {
+            for record in &released {
+                self.multicast_at
+                    .remove(&(CacheKey::of(record), record.rdata().to_vec()));
+            }
+            self.send_unsolicited(goodbyes(released.iter()));
+        }
+    }
+
+    /// Stops answering for the records registered under `id`, sending goodbyes for them if they
+    /// had been announced.
+    pub fn unregister(&mut self, id: RegistrationId) {
+        let Some(index) = self.registrations.iter().position(|r| r.id == id) else {
+            return;
+        };
+
+        let registration = self.registrations.remove(index);
+
+        // Records other registrations also hold, like the `_services._dns-sd._udp` PTR shared by
+        // services of the same type, live on, even while those are still probing.
+        let released = registration
+            .records
+            .iter()
+            .map(|r| &r.record)
+            .filter(|record| !self.holds(record))
+            .collect::<Vec<_>>();
+
+        if !registration.state.is_probing() && !released.is_empty() {
+            self.send_unsolicited(goodbyes(released.iter().copied()));
+        }
+        for record in released {
+            let key = (CacheKey::of(record), record.rdata().to_vec());
+            self.multicast_at.remove(&key);
+        }
+    }
+
+    /// Sends goodbyes for everything registered, and forgets it all.
+    pub fn shutdown(&mut self) {
+        let ids = self.registrations.iter().map(|r| r.id).collect::<Vec<_>>();
+        ids.into_iter().for_each(|id| self.unregister(id));
+    }
+
+    /// Every record currently being answered for.
+    pub fn records(&self) -> impl Iterator<Item = &AuthoritativeRecord> {
+        self.registrations
+            .iter()
+            .filter(|r| !r.state.is_probing())
+            .flat_map(|r| r.records.iter())
+    }
+
+    /// Answers a query from `src`, which is a legacy unicast query if it isn't from port 5353.
+    pub fn handle_query(&mut self, query: &MDNSResponse, src: SocketAddr, now: Instant) {
+        self.advance(now);
+        if query.header.is_response() {
+            return;
+        }
+        if !query.authorities.is_empty() {
+            self.tiebreak(query, now);
+        }
+
+        /* https://www.rfc-editor.org/rfc/rfc6762.html#section-7.2
+            If the TC bit is set, the responder SHOULD delay its response by a random amount
+            of time selected with uniform random distribution in the range 400-500 ms, to
+            allow enough time for the multi-packet query to arrive ...
+        */
+        if let Some(truncated) = self.truncated.get_mut(&src) {
+            truncated
+                .query
+                .queries
+                .extend(query.queries.iter().cloned());
+            truncated
+                .query
+                .answers
+                .extend(query.answers.iter().cloned());
+            return;
+        }
+        if query.header.is_truncated() {
+            let due = now + Duration::from_millis(self.rng.u64(TRUNCATED_DELAY_MS));
+            let query = query.clone();
+            self.truncated.insert(src, TruncatedQuery { query, due });
+            return;
+        }
+
+        self.answer(query, src, now);
+    }
+
+    /// Answers a complete query, leaving out anything it lists as a known answer.
+    fn answer(&mut self, query: &MDNSResponse, src: SocketAddr, now: Instant) {
+        /* https://www.rfc-editor.org/rfc/rfc6762.html#section-7.1
+            A Multicast DNS responder MUST NOT answer a Multicast DNS query if the answer it
+            would give is already included in the Answer Section with an RR TTL at least half
+            the correct value.
+        */
+        let known = |record: &AuthoritativeRecord| {
+            query.answers.iter().any(|theirs| {
+                theirs.same_data(&record.record) && theirs.ttl >= record.record.ttl / 2
+            })
+        };
+
+        let mut answers: Vec<&AuthoritativeRecord> = vec![];
+        let mut unicast: Vec<MDNSResourceRecord> = vec![];
+        for record in self
+            .records()
+            .filter(|r| query.queries.iter().any(|q| r.answers(q)))
+            .filter(|r| !known(r))
+        {
+            let qu = query
+                .queries
+                .iter()
+                .any(|q| q.unicast_response() && record.answers(q));
+            /* https://www.rfc-editor.org/rfc/rfc6762.html#section-5.4
+                When receiving a question with the unicast-response bit set, a responder
+                SHOULD usually respond with a unicast packet directed back to the querier.
+                However, if the responder has not multicast that record recently (within one
+                quarter of its TTL), then the responder SHOULD instead multicast the response
+                so as to keep all the peer caches up to date.
+            */
+            let quarter_ttl = Duration::from_secs(record.record.ttl as u64) / 4;
+            if qu && self.multicast_within(&record.record, quarter_ttl, now) {
+                if !unicast.contains(&record.answer()) {
+                    unicast.push(record.answer());
+                }
+            } else if !answers.contains(&record) {
+                answers.push(record);
+            }
+        }
+        let all_unique = answers.iter().all(|r| r.unique);
+        let answers = answers.iter().map(|r| r.answer()).collect::<Vec<_>>();
+
+        if src.port() != MDNS_PORT {
+            let answers = [unicast, answers].concat();
+            if !answers.is_empty() {
+                self.send_legacy_response(query, answers, src);
+            }
+            return;
+        }
+
+        if !unicast.is_empty() {
+            self.send_response(unicast, Destination::Unicast(src), now);
+        }
+
+        // Answers multicast too recently wait until they may be multicast again.
+        let interval = match query.authorities.is_empty() {
+            true => MULTICAST_INTERVAL,
+            false => PROBE_MULTICAST_INTERVAL,
+        };
+        let (limited, answers) = answers
+            .into_iter()
+            .partition::<Vec<_>, _>(|answer| self.multicast_within(answer, interval, now));
+        for answer in limited {
+            let due = self.next_multicast(&answer, interval, now);
+            self.pending.schedule(answer, due);
+        }
+        if answers.is_empty() {
+            return;
+        }
+
+        // A response made up only of unique records needn't wait for other responders.
+        if all_unique {
+            self.send_response(answers, Destination::Multicast, now);
+            return;
+        }
+
+        let due = now + Duration::from_millis(self.rng.u64(SHARED_DELAY_MS));
+        for answer in answers {
+            self.pending.schedule(answer, due);
+        }
+    }
+
+    /// Watches another host's response, both so we don't repeat its answers and to notice it
+    /// defending a name we are probing for.
+    pub fn handle_response(&mut self, response: &MDNSResponse, now: Instant) {
+        self.advance(now);
+        self.pending.observe_response(response);
+
+        let records = response
+            .answers
+            .iter()
+            .chain(response.additional.iter())
+            .collect::<Vec<_>>();
+        let conflicted = self
+            .registrations
+            .iter()
+            .enumerate()
+            .filter_map(|(i, registration)| {
+                // Responses from before our first probe can't be about it, see RFC 6762 section
+                // 8.1.
+                let State::Probing { sent: 1.., .. } = registration.state else {
+                    return None;
+                };
+
+                let proposed = registration.unique_records();
+                let theirs = records.iter().find(|theirs| conflicts(&proposed, theirs))?;
+                proposed
+                    .into_iter()
+                    .find(|ours| ours.rr_name.eq_ignore_ascii_case(&theirs.rr_name))
+                    .map(|ours| (i, ours.rr_name))
+            })
+            .collect::<Vec<_>>();
+
+        for (i, name) in conflicted {
+            self.rename(i, &name, now);
+        }
+
+        self.check_live_records(&records, now);
+    }
+
+    /* https://www.rfc-editor.org/rfc/rfc6762.html#section-9
+        Whenever a Multicast DNS responder receives any Multicast DNS response (solicited
+        or otherwise) containing a conflicting resource record, the conflict MUST be
+        resolved ... the host MUST immediately reset its conflicted unique record(s) to
+        probing state, and then re-probe.
+    */
+    /// Re-probes registrations whose unique records another host contradicts, and re-announces
+    /// records another host is about to let expire. Identical records are not conflicts.
+    fn check_live_records(&mut self, records: &[&MDNSResourceRecord], now: Instant) {
+        let mut defend = vec![];
+
+        for registration in &mut self.registrations {
+            if registration.state.is_probing() {
+                continue;
+            }
+
+            let contradicted = records
+                .iter()
+                .any(|theirs| conflicts(&registration.unique_records(), theirs));
+            if contradicted {
+                registration.state = State::Probing {
+                    sent: 0,
+                    next: now + Duration::from_millis(self.rng.u64(INITIAL_DELAY_MS)),
+                };
+                self.events
+                    .push_back(ResponderEvent::Conflict(registration.id));
+                continue;
+            }
+
+            // See RFC 6762 section 6.6, a stale copy of our record would soon be dropped by caches.
+            for ours in &registration.records {
+                let stale = records.iter().any(|theirs| {
+                    theirs.same_data(&ours.record) && theirs.ttl < ours.record.ttl / 2
+                });
+                if stale && !defend.contains(&ours.answer()) {
+                    defend.push(ours.answer());
+                }
+            }
+        }
+
+        if !defend.is_empty() {
+            self.note_multicast(&defend, now);
+            self.send_unsolicited(defend);
+        }
+    }
+
+    pub fn handle_timeout(&mut self, now: Instant) {
+        self.advance(now);
+        let dropped = self
+            .registrations
+            .iter()
+            .filter(|r| r.is_dropped())
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+        dropped.into_iter().for_each(|id| self.unregister(id));
+
+        self.probe(now);
+        self.announce(now);
+
+        let due = self
+            .truncated
+            .iter()
+            .filter(|(_, t)| t.due <= now)
+            .map(|(src, _)| *src)
+            .collect::<Vec<_>>();
+        for src in due {
+            if let Some(truncated) = self.truncated.remove(&src) {
+                self.answer(&truncated.query, src, now);
+            }
+        }
+
+        // Answers are scheduled for after any interval they had to wait out, but the record may
+        // have been multicast since, such as in an announcement.
+        let (limited, due) = self
+            .pending
+            .poll_due(now)
+            .into_iter()
+            .partition::<Vec<_>, _>(|answer| {
+                self.multicast_within(answer, PROBE_MULTICAST_INTERVAL, now)
+            });
+        for answer in limited {
+            let due = self.next_multicast(&answer, PROBE_MULTICAST_INTERVAL, now);
+            self.pending.schedule(answer, due);
+        }
+        if !due.is_empty() {
+            self.send_response(due, Destination::Multicast, now);
+        }
+    }
+
+    /// The next point in time at which [`Responder::handle_timeout`] should be called.
+    ///
+    /// Once a handle is dropped this is due straight away, so its goodbyes go out without
+    /// waiting for anything else to happen.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let dropped = self
+            .registrations
+            .iter()
+            .any(Registration::is_dropped)
+            .then_some(self.latest)
+            .flatten();
+
+        self.registrations
+            .iter()
+            .filter_map(|r| r.state.next())
+            .chain(dropped)
+            .chain(self.truncated.values().map(|t| t.due))
+            .chain(self.pending.next_due())
+            .min()
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<ResponderEvent> {
+        self.events.pop_front()
+    }
+
+    /// Whether any registration, probing or not, holds `record`.
+    fn holds(&self, record: &MDNSResourceRecord) -> bool {
+        self.registrations
+            .iter()
+            .flat_map(|r| r.records.iter())
+            .any(|r| r.record.same_data(record))
+    }
+
+    fn advance(&mut self, now: Instant) {
+        self.latest = Some(self.latest.map_or(now, |latest| latest.max(now)));
+    }
+
+    /// Gives a registration a new name in place of the conflicting `name`, and probes again.
+    fn rename(&mut self, index: usize, name: &MDNSFQDN, now: Instant) {
+        self.conflicts.push_back(now);
+        while self
+            .conflicts
+            .front()
+            .is_some_and(|t| *t + CONFLICT_WINDOW < now)
+        {
+            self.conflicts.pop_front();
+        }
+
+        let delay = if self.conflicts.len() >= CONFLICT_LIMIT {
+            RATE_LIMITED_DELAY
+        } else {
+            Duration::from_millis(self.rng.u64(INITIAL_DELAY_MS))
+        };
+
+        let registration = &mut self.registrations[index];
+        let renamed = next_name(name);
+        rename_records(&mut registration.records, name, &renamed);
+        registration.state = State::Probing {
+            sent: 0,
+            next: now + delay,
+        };
+
+        self.events.push_back(ResponderEvent::Renamed {
+            id: registration.id,
+            from: name.clone(),
+            to: renamed,
+        });
+    }
+
+    /// Makes any of our probing registrations that lose the tiebreak against another host's
+    /// simultaneous probe wait a second and start probing again, see RFC 6762 section 8.2.
+    fn tiebreak(&mut self, probe: &MDNSResponse, now: Instant) {
+        for registration in &mut self.registrations {
+            let State::Probing { .. } = registration.state else {
+                continue;
+            };
+
+            let proposed = registration.unique_records();
+            let lost = proposed.iter().any(|ours| {
+                probe
+                    .authorities
+                    .iter()
+                    .any(|theirs| theirs.rr_name.eq_ignore_ascii_case(&ours.rr_name))
+                    && tiebreak(&ours.rr_name, &proposed, &probe.authorities).is_lt()
+            });
+
+            if lost {
+                registration.state = State::Probing {
+                    sent: 0,
+                    next: now + TIEBREAK_DELAY,
+                };
+            }
+        }
+    }
+
+    /// Sends any due probes, and establishes registrations that have finished probing.
+    fn probe(&mut self, now: Instant) {
+        for registration in &mut self.registrations {
+            let State::Probing { sent, next } = registration.state else {
+                continue;
+            };
+            if next > now {
+                continue;
+            }
+
+            if sent == PROBE_COUNT {
+                registration.state = State::Announcing { sent: 0, next: now };
+                self.events
+                    .push_back(ResponderEvent::Registered(registration.id));
+                continue;
+            }
+
+            let packet = probe_packet(&registration.unique_records(), sent == 0);
+            self.transmits.push_back(Transmit::multicast(packet.pack()));
+            registration.state = State::Probing {
+                sent: sent + 1,
+                next: now + PROBE_INTERVAL,
+            };
+        }
+    }
+
+    /// Sends any due announcements, see RFC 6762 section 8.3.
+    fn announce(&mut self, now: Instant) {
+        let mut announcements = vec![];
+
+        for registration in &mut self.registrations {
+            let State::Announcing { sent, next } = registration.state else {
+                continue;
+            };
+            if next > now {
+                continue;
+            }
+
+            for answer in registration.records.iter().map(|r| r.answer()) {
+                if !announcements.contains(&answer) {
+                    announcements.push(answer);
+                }
+            }
+            registration.state = match sent + 1 {
+                ANNOUNCE_COUNT => State::Established,
+                sent => State::Announcing {
+                    sent,
+                    next: now + announce_interval(sent),
+                },
+            };
+        }
+
+        if !announcements.is_empty() {
+            self.note_multicast(&announcements, now);
+            self.send_unsolicited(announcements);
+        }
+    }
+
+    /// Sends records nobody asked for, such as announcements and goodbyes.
+    fn send_unsolicited(&mut self, records: Vec<MDNSResourceRecord>) {
+        for response in split_response(records, vec![]) {
+            self.transmits
+                .push_back(Transmit::multicast(response.pack()));
+        }
+    }
+
+    fn send_response(
+        &mut self,
+        answers: Vec<MDNSResourceRecord>,
+        destination: Destination,
+        now: Instant,
+    ) {
+        let additional = self.additional_records(&answers);
+        if destination == Destination::Multicast {
+            self.note_multicast(&answers, now);
+            self.note_multicast(&additional, now);
+        }
+
+        for response in split_response(answers, additional) {
+            self.transmits.push_back(Transmit {
+                destination: destination.clone(),
+                packet: response.pack().into_vec(),
+            });
+        }
+    }
+
+    fn note_multicast(&mut self, records: &[MDNSResourceRecord], now: Instant) {
+        for record in records {
+            let key = (CacheKey::of(record), record.rdata().to_vec());
+            self.multicast_at.insert(key, now);
+        }
+    }
+
+    /// Whether `record` was multicast less than `interval` ago.
+    fn multicast_within(
+        &self,
+        record: &MDNSResourceRecord,
+        interval: Duration,
+        now: Instant,
+    ) -> bool {
+        self.next_multicast(record, interval, now) > now
+    }
+
+    /// When `record` may be multicast again, `interval` after it last was.
+    fn next_multicast(
+        &self,
+        record: &MDNSResourceRecord,
+        interval: Duration,
+        now: Instant,
+    ) -> Instant {
+        let key = (CacheKey::of(record), record.rdata().to_vec());
+
+        self.multicast_at
+            .get(&key)
+            .map_or(now, |at| now.max(*at + interval))
+    }
+
+    /* https://www.rfc-editor.org/rfc/rfc6762.html#section-6.7
+        If the source UDP port in a received Multicast DNS query is not port 5353, this
+        indicates that the querier originating the query is a simple resolver ... the
+        Multicast DNS responder MUST send a UDP response directly back to the querier,
+        via unicast, to the query packet's source IP address and port. This unicast
+        response MUST be a conventional unicast response as would be generated by a
+        conventional Unicast DNS server; for example, it MUST repeat the query ID and the
+        question given in the query message. In addition, the cache-flush bit described
+        in Section 10.2 MUST NOT be set in legacy unicast responses.
+    */
+    fn send_legacy_response(
+        &mut self,
+        query: &MDNSResponse,
+        answers: Vec<MDNSResourceRecord>,
+        src: SocketAddr,
+    ) {
+        let legacy = |mut record: MDNSResourceRecord| {
+            record.cache_flush_rr_class &= !CLASS_TOP_BIT;
+            record.ttl = record.ttl.min(LEGACY_MAX_TTL);
+            record
+        };
+
+        let additional = self.additional_records(&answers);
+        let mut response = MDNSResponse::authoritative(
+            answers.into_iter().map(legacy).collect(),
+            additional.into_iter().map(legacy).collect(),
+        );
+        response.header.transaction_id = query.header.transaction_id;
+        response.header.questions = query.queries.len() as u16;
+        response.queries = query.queries.clone();
+
+        self.transmits.push_back(Transmit {
+            destination: Destination::Unicast(src),
+            packet: response.pack().into_vec(),
+        });
+    }
+
+    /// Records that the querier is likely to ask for next, see RFC 6763 section 12.
+    fn additional_records(&self, answers: &[MDNSResourceRecord]) -> Vec<MDNSResourceRecord> {
+        let mut names: Vec<(MDNSFQDN, MDNSTYPE)> = vec![];
+        let addresses = |names: &mut Vec<_>, host: &MDNSFQDN| {
+            names.push((host.clone(), MDNSTYPE::A));
+            names.push((host.clone(), MDNSTYPE::AAAA));
+        };
+
+        for answer in answers {
+            match answer.rr_type {
+                MDNSTYPE::PTR => {
+                    let Ok(instance) = answer.rdata_as::<MDNSFQDN>() else {
+                        continue;
+                    };
+                    names.push((instance.clone(), MDNSTYPE::SRV));
+                    names.push((instance.clone(), MDNSTYPE::TXT));
+
+                    let targets = self
+                        .records()
+                        .filter(|r| r.record.rr_type == MDNSTYPE::SRV)
+                        .filter(|r| r.record.rr_name.eq_ignore_ascii_case(&instance))
+                        .filter_map(|r| r.record.rdata_as::<SRV>().ok())
+                        .collect::<Vec<_>>();
+                    targets
+                        .iter()
+                        .for_each(|srv| addresses(&mut names, &srv.target));
+                }
+                MDNSTYPE::SRV => {
+                    if let Ok(srv) = answer.rdata_as::<SRV>() {
+                        addresses(&mut names, &srv.target);
+                    }
+                }
+                MDNSTYPE::A | MDNSTYPE::AAAA => addresses(&mut names, &answer.rr_name),
+                _ => {}
+            }
+        }
+
+        let mut additional: Vec<MDNSResourceRecord> = vec![];
+        for record in self.records() {
+            let wanted = names.iter().any(|(name, ty)| {
+                *ty == record.record.rr_type && name.eq_ignore_ascii_case(&record.record.rr_name)
+            });
+            let answer = record.answer();
+            if wanted
+                && !answers.iter().any(|a| a.same_data(&answer))
+                && !additional.contains(&answer)
+            {
+                additional.push(answer);
+            }
+        }
+
+        additional
+    }
+}
+
+/// Adds a unique reverse-mapping PTR record for every unique address record, unless present.
+fn with_reverse_records(mut records: Vec<AuthoritativeRecord>) -> Vec<AuthoritativeRecord> {
+    let reverse = records
+        .iter()
+        .filter(|r| r.unique)
+        .filter_map(|r| {
+            let address = address(r.record.rr_type, r.record.rdata())?;
+            let rdata = r.record.rr_name.pack().into_vec();
+            let ptr = MDNSResourceRecord::new(
+                reverse_name(address),
+                MDNSTYPE::PTR,
+                false,
+                r.record.ttl,
+                rdata,
+            );
+            Some(AuthoritativeRecord::unique(ptr))
+        })
+        .collect::<Vec<_>>();
+
+    for ptr in reverse {
+        if !records.iter().any(|r| r.record.same_data(&ptr.record)) {
+            records.push(ptr);
+        }
+    }
+    records
+}
+
+/// Packs answers into as few responses as fit in [`MAX_PACKET_SIZE`], followed by whichever
+/// additional records still fit in the last one.
+fn split_response(
+    answers: Vec<MDNSResourceRecord>,
+    additional: Vec<MDNSResourceRecord>,
+) -> Vec<MDNSResponse> {
+    let header = MDNSResponse::authoritative(vec![], vec![]).pack().len() / 8;
+    let mut responses = vec![];
+    let mut current = vec![];
+    let mut size = header;
+
+    for answer in answers {
+        let len = answer.pack().len() / 8;
+        if size + len > MAX_PACKET_SIZE && !current.is_empty() {
+            responses.push(MDNSResponse::authoritative(
+                std::mem::take(&mut current),
+                vec![],
+            ));
+            size = header;
+        }
+        size += len;
+        current.push(answer);
+    }
+
+    let mut extra = vec![];
+    for record in additional {
+        let len = record.pack().len() / 8;
+        if size + len <= MAX_PACKET_SIZE {
+            size += len;
+            extra.push(record);
+        }
+    }
+    responses.push(MDNSResponse::authoritative(current, extra));
+
+    responses
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use bitvec::view::BitView;
+
+    use super::*;
+    use crate::{
+        dns_sd::{ServiceInstanceName, Subtype},
+        packets::{
+            header::FLAG_TC,
+            packet::MDNSPacket,
+            rdata::{HINFO, TXT},
+            CLASS_IN,
+        },
+    };
+
+    pub(crate) fn unpack(transmit: &Transmit) -> MDNSResponse {
+        MDNSResponse::unpack(&mut transmit.packet.view_bits().to_bitvec()).unwrap()
+    }
+
+    /// Drives `responder` through its timeouts until it has nothing left to do, returning every
+    /// packet it sent and when. With `loopback` it also hears its own packets from there, as it
+    /// does with multicast loopback on.
+    pub(crate) fn settle(
+        responder: &mut Responder,
+        loopback: Option<SocketAddr>,
+    ) -> Vec<(Instant, Transmit)> {
+        let mut sent = vec![];
+        for _ in 0..1000 {
+            let Some(at) = responder.poll_timeout() else {
+                break;
+            };
+            responder.handle_timeout(at);
+            while let Some(transmit) = responder.poll_transmit() {
+                if let Some(src) = loopback {
+                    let packet = unpack(&transmit);
+                    match packet.header.is_response() {
+                        true => responder.handle_response(&packet, at),
+                        false => responder.handle_query(&packet, src, at),
+                    }
+                }
+                sent.push((at, transmit));
+            }
+        }
+        assert_eq!(responder.poll_timeout(), None, "responder never settled");
+
+        sent
+    }
+
+    pub(crate) fn query(name: &str, qtype: MDNSTYPE) -> MDNSResponse {
+        let packet = MDNSPacket::with_queries(vec![MDNSQuery::from_fqdn(
+            MDNSFQDN::new(name),
+            qtype,
+            false,
+        )]);
+        MDNSResponse::unpack(&mut packet.pack()).unwrap()
+    }
+
+    pub(crate) fn service_records() -> Vec<AuthoritativeRecord> {
+        let instance = MDNSFQDN::new("Printer._http._tcp.local");
+        let host = MDNSFQDN::new("printer.local");
+        let srv = SRV {
+            priority: 0,
+            weight: 0,
+            port: 80,
+            target: host.clone(),
+        };
+
+        vec![
+            AuthoritativeRecord::shared(MDNSResourceRecord::new(
+                MDNSFQDN::new("_http._tcp.local"),
+                MDNSTYPE::PTR,
+                false,
+                4500,
+                instance.pack().into_vec(),
+            )),
+            AuthoritativeRecord::unique(MDNSResourceRecord::new(
+                instance.clone(),
+                MDNSTYPE::SRV,
+                false,
+                120,
+                srv.pack().into_vec(),
+            )),
+            AuthoritativeRecord::unique(MDNSResourceRecord::new(
+                instance,
+                MDNSTYPE::TXT,
+                false,
+                4500,
+                vec![0],
+            )),
+            AuthoritativeRecord::unique(MDNSResourceRecord::new(
+                host,
+                MDNSTYPE::A,
+                false,
+                120,
+                Ipv4Addr::new(192, 168, 1, 20).octets().to_vec(),
+            )),
+        ]
+    }
+
+    #[test]
+    fn test_answers_with_additional_records() {
+        let start = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let mut responder = Responder::with_seed(0);
+        responder.register(service_records(), start);
+        settle(&mut responder, None);
+        let now = start + Duration::from_secs(10);
+
+        // Unique answers go out straight away, with the address as an additional record.
+        responder.handle_query(&query("printer._http._tcp.LOCAL", MDNSTYPE::SRV), src, now);
+        let response = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(response.answers.len(), 1);
+        assert!(response.answers[0].cache_flush());
+        assert_eq!(response.answers[0].class(), CLASS_IN);
+        assert_eq!(response.additional.len(), 1);
+        assert_eq!(response.additional[0].rr_type, MDNSTYPE::A);
+
+        // Shared answers wait 20-120ms.
+        responder.handle_query(&query("_http._tcp.local", MDNSTYPE::PTR), src, now);
+        assert!(responder.poll_transmit().is_none());
+        let due = responder.poll_timeout().unwrap();
+        assert!(due >= now + Duration::from_millis(20));
+        assert!(due <= now + Duration::from_millis(120));
+
+        responder.handle_timeout(due);
+        let response = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(
+            response.answers[0].rdata_as::<MDNSFQDN>().unwrap(),
+            MDNSFQDN::new("Printer._http._tcp.local")
+        );
+        let mut types = response.additional.iter().map(|r| r.rr_type);
+        assert!(types.all(|t| [MDNSTYPE::SRV, MDNSTYPE::TXT, MDNSTYPE::A].contains(&t)));
+        assert_eq!(response.additional.len(), 3);
+    }
+
+    #[test]
+    fn test_probing() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(0);
+        let id = responder.register(service_records(), now);
+
+        let mut probes = vec![];
+        loop {
+            let at = responder.poll_timeout().unwrap();
+            responder.handle_timeout(at);
+            if let Some(transmit) = responder.poll_transmit() {
+                let packet = unpack(&transmit);
+                if !packet.header.is_response() {
+                    probes.push((at, packet));
+                }
+            }
+            if let Some(event) = responder.poll_event() {
+                assert_eq!(event, ResponderEvent::Registered(id));
+                break;
+            }
+
+            // Nothing is answered while probing.
+            let src = "192.168.1.10:5353".parse().unwrap();
+            responder.handle_query(&query("printer.local", MDNSTYPE::A), src, at);
+            assert!(responder.poll_transmit().is_none());
+        }
+
+        assert_eq!(probes.len(), 3);
+        assert!(probes[0].0 <= now + Duration::from_millis(250));
+        assert_eq!(probes[2].0 - probes[0].0, PROBE_INTERVAL * 2);
+        for (i, (_, probe)) in probes.iter().enumerate() {
+            assert_eq!(probe.queries.len(), 3);
+            assert!(probe.queries.iter().all(|q| q.qtype == MDNSTYPE::ANY));
+            assert!(probe
+                .queries
+                .iter()
+                .all(|q| q.unicast_response() == (i == 0)));
+            assert_eq!(probe.authorities.len(), 4);
+        }
+        assert!(responder.records().count() == 5);
+
+        // Whereas another host defending one of the names makes us pick another.
+        let mut responder = Responder::with_seed(0);
+        let id = responder.register(service_records(), now);
+        responder.handle_timeout(responder.poll_timeout().unwrap());
+
+        let defence = MDNSResourceRecord::new(
+            MDNSFQDN::new("printer.local"),
+            MDNSTYPE::A,
+            true,
+            120,
+            vec![192, 168, 1, 99],
+        );
+        responder.handle_response(&MDNSResponse::authoritative(vec![defence], vec![]), now);
+        assert_eq!(
+            responder.poll_event(),
+            Some(ResponderEvent::Renamed {
+                id,
+                from: MDNSFQDN::new("printer.local"),
+                to: MDNSFQDN::new("printer-2.local"),
+            })
+        );
+
+        while responder.poll_event().is_none() {
+            responder.handle_timeout(responder.poll_timeout().unwrap());
+        }
+        while responder.poll_transmit().is_some() {}
+
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let later = now + Duration::from_secs(10);
+        responder.handle_query(&query("printer-2.local", MDNSTYPE::A), src, later);
+        assert!(responder.poll_transmit().is_some());
+        responder.handle_query(
+            &query("Printer._http._tcp.local", MDNSTYPE::SRV),
+            src,
+            later,
+        );
+        let srv = unpack(&responder.poll_transmit().unwrap()).answers[0].rdata_as::<SRV>();
+        assert_eq!(srv.unwrap().target, MDNSFQDN::new("printer-2.local"));
+    }
+
+    #[test]
+    fn test_renaming_service_instances() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(0);
+        let id = responder.register(service_records(), now);
+        let at = responder.poll_timeout().unwrap();
+        responder.handle_timeout(at);
+
+        // Another host already has an instance of the same name, on another port.
+        let srv = SRV {
+            priority: 0,
+            weight: 0,
+            port: 8080,
+            target: MDNSFQDN::new("other.local"),
+        };
+        let theirs = MDNSResourceRecord::new(
+            MDNSFQDN::new("Printer._http._tcp.local"),
+            MDNSTYPE::SRV,
+            true,
+            120,
+            srv.pack().into_vec(),
+        );
+        responder.handle_response(&MDNSResponse::authoritative(vec![theirs], vec![]), at);
+        let renamed = MDNSFQDN::new("Printer (2)._http._tcp.local");
+        assert_eq!(
+            responder.poll_event(),
+            Some(ResponderEvent::Renamed {
+                id,
+                from: MDNSFQDN::new("Printer._http._tcp.local"),
+                to: renamed.clone(),
+            })
+        );
+
+        settle(&mut responder, None);
+        assert_eq!(responder.poll_event(), Some(ResponderEvent::Registered(id)));
+        let ptr = responder
+            .records()
+            .find(|r| r.record.rr_type == MDNSTYPE::PTR)
+            .unwrap();
+        assert_eq!(ptr.record.rdata_as::<MDNSFQDN>().unwrap(), renamed);
+        assert!(responder
+            .records()
+            .filter(|r| matches!(r.record.rr_type, MDNSTYPE::SRV | MDNSTYPE::TXT))
+            .all(|r| r.record.rr_name == renamed));
+    }
+
+    #[test]
+    fn test_conflict_rate_limiting() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(0);
+        let mut name = MDNSFQDN::new("printer.local");
+        let record = |name: &MDNSFQDN, octet| {
+            let rdata = vec![192, 168, 1, octet];
+            MDNSResourceRecord::new(name.clone(), MDNSTYPE::A, true, 120, rdata)
+        };
+        responder.register(vec![AuthoritativeRecord::unique(record(&name, 20))], now);
+
+        // Every name we try is taken, so each first probe draws a defence.
+        for conflict in 1..=CONFLICT_LIMIT + 1 {
+            let at = responder.poll_timeout().unwrap();
+            responder.handle_timeout(at);
+            let defence = MDNSResponse::authoritative(vec![record(&name, 99)], vec![]);
+            responder.handle_response(&defence, at);
+            let Some(ResponderEvent::Renamed { to, .. }) = responder.poll_event() else {
+                panic!("no rename after conflict {conflict}");
+            };
+            name = to;
+
+            let delay = responder.poll_timeout().unwrap() - at;
+            match conflict < CONFLICT_LIMIT {
+                true => assert!(delay <= Duration::from_millis(250)),
+                false => assert_eq!(delay, RATE_LIMITED_DELAY),
+            }
+        }
+        assert_eq!(name, MDNSFQDN::new("printer-17.local"));
+    }
+
+    #[test]
+    fn test_announcements_and_goodbyes() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(0);
+        let id = responder.register(service_records(), now);
+
+        let announcements = settle(&mut responder, None)
+            .into_iter()
+            .map(|(at, transmit)| (at, unpack(&transmit)))
+            .filter(|(_, packet)| packet.header.is_response())
+            .collect::<Vec<_>>();
+
+        assert_eq!(announcements.len(), 3);
+        let (start, _) = announcements[0];
+        assert_eq!(announcements[1].0 - start, Duration::from_secs(1));
+        assert_eq!(announcements[2].0 - start, Duration::from_secs(3));
+        for (_, announcement) in &announcements {
+            assert_eq!(announcement.answers.len(), 5);
+            assert!(announcement.answers.iter().all(|r| r.cache_flush()
+                == (r.rr_type != MDNSTYPE::PTR || r.rr_name.to_string().ends_with(".arpa"))));
+        }
+
+        responder.unregister(id);
+        let goodbye = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(goodbye.answers.len(), 5);
+        assert!(goodbye.answers.iter().all(|r| r.ttl == 0));
+        assert_eq!(responder.records().count(), 0);
+    }
+
+    #[test]
+    fn test_conflicts_after_registration() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(0);
+        let id = responder.register(service_records(), now);
+        settle(&mut responder, None);
+        while responder.poll_event().is_some() {}
+
+        let address = |ttl, octet| {
+            let rdata = vec![192, 168, 1, octet];
+            MDNSResourceRecord::new(
+                MDNSFQDN::new("printer.local"),
+                MDNSTYPE::A,
+                true,
+                ttl,
+                rdata,
+            )
+        };
+        let response = |record| MDNSResponse::authoritative(vec![record], vec![]);
+
+        // Our own data coming back is benign.
+        responder.handle_response(&response(address(120, 20)), now);
+        assert!(responder.poll_transmit().is_none());
+        assert!(responder.poll_event().is_none());
+
+        // Unless it is about to expire, in which case we announce it again.
+        responder.handle_response(&response(address(0, 20)), now);
+        let defence = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(defence.answers, [address(120, 20)]);
+
+        // Conflicting data makes us probe again, and as nobody defends it we keep the name.
+        responder.handle_response(&response(address(120, 99)), now);
+        assert_eq!(responder.poll_event(), Some(ResponderEvent::Conflict(id)));
+        assert_eq!(responder.records().count(), 0);
+
+        while responder.poll_event().is_none() {
+            responder.handle_timeout(responder.poll_timeout().unwrap());
+        }
+        assert_eq!(responder.records().count(), 5);
+    }
+
+    #[test]
+    fn test_unicast_responses() {
+        let now = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let mut responder = Responder::with_seed(0);
+        responder.register(service_records(), now);
+        let (announced, _) = settle(&mut responder, None).pop().unwrap();
+
+        let mut qu = query("printer.local", MDNSTYPE::A);
+        qu.queries[0].qu_qclass |= CLASS_TOP_BIT;
+
+        // The address was just announced, so a QU question gets a unicast reply.
+        responder.handle_query(&qu, src, announced + Duration::from_secs(29));
+        let transmit = responder.poll_transmit().unwrap();
+        assert_eq!(transmit.destination, Destination::Unicast(src));
+        assert_eq!(unpack(&transmit).answers[0].rr_type, MDNSTYPE::A);
+
+        // After a quarter of its TTL it is multicast again instead, to refresh every cache.
+        responder.handle_query(&qu, src, announced + Duration::from_secs(30));
+        let transmit = responder.poll_transmit().unwrap();
+        assert_eq!(transmit.destination, Destination::Multicast);
+        responder.handle_query(&qu, src, announced + Duration::from_secs(31));
+        let transmit = responder.poll_transmit().unwrap();
+        assert_eq!(transmit.destination, Destination::Unicast(src));
+    }
+
+    #[test]
+    fn test_truncated_known_answers() {
+        let now = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let service = MDNSFQDN::new("_http._tcp.local");
+        let ptr = |instance: &str, ttl| {
+            let rdata = MDNSFQDN::new(instance).pack().into_vec();
+            MDNSResourceRecord::new(service.clone(), MDNSTYPE::PTR, false, ttl, rdata)
+        };
+
+        let mut responder = Responder::with_seed(0);
+        responder.register(service_records(), now);
+        responder.register(
+            vec![AuthoritativeRecord::shared(ptr(
+                "Scanner._http._tcp.local",
+                4500,
+            ))],
+            now,
+        );
+        settle(&mut responder, None);
+        let now = now + Duration::from_secs(10);
+
+        // The known answers arrive over two packets, the first flagged as truncated.
+        let mut first = query("_http._tcp.local", MDNSTYPE::PTR);
+        first.header.flags |= FLAG_TC;
+        first.answers = vec![ptr("Printer._http._tcp.local", 4000)];
+        let mut rest = MDNSResponse::authoritative(vec![], vec![]);
+        rest.header.flags = 0;
+        rest.answers = vec![ptr("Scanner._http._tcp.local", 1000)];
+
+        responder.handle_query(&first, src, now);
+        responder.handle_query(&rest, src, now + Duration::from_millis(10));
+        responder.handle_timeout(now + Duration::from_millis(399));
+        assert!(responder.poll_transmit().is_none());
+
+        // Only the record whose known answer is past half its TTL is answered.
+        let sent = settle(&mut responder, None);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            unpack(&sent[0].1).answers,
+            [ptr("Scanner._http._tcp.local", 4500)]
+        );
+    }
+
+    #[test]
+    fn test_aggregation_and_rate_limiting() {
+        let start = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let service = MDNSFQDN::new("_http._tcp.local");
+        let mut responder = Responder::with_seed(0);
+        let records = (0..60)
+            .map(|i| {
+                let instance = MDNSFQDN::new(&format!("Device number {i:03}._http._tcp.local"));
+                let rdata = instance.pack().into_vec();
+                let ptr =
+                    MDNSResourceRecord::new(service.clone(), MDNSTYPE::PTR, false, 4500, rdata);
+                AuthoritativeRecord::shared(ptr)
+            })
+            .collect::<Vec<_>>();
+        responder.register(records, start);
+        let ipp = MDNSResourceRecord::new(
+            MDNSFQDN::new("_ipp._tcp.local"),
+            MDNSTYPE::PTR,
+            false,
+            4500,
+            MDNSFQDN::new("Printer._ipp._tcp.local").pack().into_vec(),
+        );
+        responder.register(vec![AuthoritativeRecord::shared(ipp)], start);
+        settle(&mut responder, None);
+        let now = start + Duration::from_secs(10);
+
+        // Two queries for shared records a moment apart are answered together, split to the MTU.
+        responder.handle_query(&query("_http._tcp.local", MDNSTYPE::PTR), src, now);
+        let later = now + Duration::from_millis(100);
+        responder.handle_query(&query("_ipp._tcp.local", MDNSTYPE::PTR), src, later);
+
+        let sent_at = responder.poll_timeout().unwrap();
+        responder.handle_timeout(sent_at);
+        let responses = std::iter::from_fn(|| responder.poll_transmit())
+            .map(|t| {
+                assert!(t.packet.len() <= MAX_PACKET_SIZE);
+                unpack(&t)
+            })
+            .collect::<Vec<_>>();
+        assert!(responses.len() > 1);
+        assert_eq!(responses.iter().flat_map(|r| r.answers.iter()).count(), 61);
+        assert!(responder.poll_timeout().is_none());
+
+        // The same records aren't multicast again within a second, but are once it has passed.
+        let at = now + Duration::from_millis(900);
+        responder.handle_query(&query("_http._tcp.local", MDNSTYPE::PTR), src, at);
+        let sent = settle(&mut responder, None);
+        assert!(sent
+            .iter()
+            .all(|(at, _)| *at >= sent_at + MULTICAST_INTERVAL));
+        let answers = sent
+            .iter()
+            .map(|(_, t)| unpack(t).answers.len())
+            .sum::<usize>();
+        assert_eq!(answers, 60);
+    }
+
+    #[test]
+    fn test_rate_limited_answers_are_deferred() {
+        let now = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let mut responder = Responder::with_seed(0);
+        responder.register(service_records(), now);
+        settle(&mut responder, None);
+        let now = now + Duration::from_secs(10);
+
+        let srv = query("Printer._http._tcp.local", MDNSTYPE::SRV);
+        responder.handle_query(&srv, src, now);
+        assert!(responder.poll_transmit().is_some());
+
+        // Asked again straight after, the answer waits out the second rather than being lost.
+        responder.handle_query(&srv, src, now + Duration::from_millis(100));
+        assert!(responder.poll_transmit().is_none());
+        assert_eq!(responder.poll_timeout(), Some(now + MULTICAST_INTERVAL));
+        responder.handle_timeout(now + MULTICAST_INTERVAL);
+        let response = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].rr_type, MDNSTYPE::SRV);
+        assert_eq!(responder.poll_timeout(), None);
+    }
+
+    #[test]
+    fn test_unsolicited_responses_fit_the_mtu() {
+        let now = Instant::now();
+        let service = MDNSFQDN::new("_http._tcp.local");
+        let records = (0..60)
+            .map(|i| {
+                let instance = MDNSFQDN::new(&format!("Device number {i:03}._http._tcp.local"));
+                let rdata = instance.pack().into_vec();
+                let ptr =
+                    MDNSResourceRecord::new(service.clone(), MDNSTYPE::PTR, false, 4500, rdata);
+                AuthoritativeRecord::shared(ptr)
+            })
+            .collect::<Vec<_>>();
+
+        let mut responder = Responder::with_seed(0);
+        let id = responder.register(records, now);
+        let sent = settle(&mut responder, None);
+        let (at, _) = sent[0];
+        let first = sent.iter().filter(|(t, _)| *t == at).collect::<Vec<_>>();
+        assert!(first.len() > 1);
+        assert!(first.iter().all(|(_, t)| t.packet.len() <= MAX_PACKET_SIZE));
+        let announced = first
+            .iter()
+            .map(|(_, t)| unpack(t).answers.len())
+            .sum::<usize>();
+        assert_eq!(announced, 60);
+
+        responder.unregister(id);
+        let goodbyes = std::iter::from_fn(|| responder.poll_transmit()).collect::<Vec<_>>();
+        assert!(goodbyes.len() > 1);
+        assert!(goodbyes.iter().all(|t| t.packet.len() <= MAX_PACKET_SIZE));
+        let gone = goodbyes
+            .iter()
+            .map(|t| unpack(t).answers.len())
+            .sum::<usize>();
+        assert_eq!(gone, 60);
+    }
+
+    #[test]
+    fn test_register_service() {
+        let now = Instant::now();
+        let service = ServiceInfo {
+            name: ServiceInstanceName::new("Mr. Printer", "_ipp._tcp".parse().unwrap(), "local")
+                .unwrap(),
+            port: 631,
+            host: MDNSFQDN::new("printer.local"),
+            txt: TXT::from_pairs([("rp", Some(&b"ipp/print"[..]))]).unwrap(),
+            subtypes: vec![Subtype::new("_universal").unwrap()],
+            addresses: vec![Ipv4Addr::new(192, 168, 1, 20).into()],
+        };
+
+        let mut responder = Responder::with_seed(0);
+        let handle = responder.register_service(&service, now);
+        let (last, _) = settle(&mut responder, None).pop().unwrap();
+        assert_eq!(
+            responder.poll_event(),
+            Some(ResponderEvent::Registered(handle.id))
+        );
+
+        let names = responder
+            .records()
+            .map(|r| (r.record.rr_name.to_string(), r.record.rr_type, r.unique))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("_ipp._tcp.local".to_string(), MDNSTYPE::PTR, false),
+                (
+                    "Mr. Printer._ipp._tcp.local".to_string(),
+                    MDNSTYPE::SRV,
+                    true
+                ),
+                (
+                    "Mr. Printer._ipp._tcp.local".to_string(),
+                    MDNSTYPE::TXT,
+                    true
+                ),
+                (
+                    "_universal._sub._ipp._tcp.local".to_string(),
+                    MDNSTYPE::PTR,
+                    false
+                ),
+                (
+                    "_services._dns-sd._udp.local".to_string(),
+                    MDNSTYPE::PTR,
+                    false
+                ),
+                ("printer.local".to_string(), MDNSTYPE::A, true),
+                ("20.1.168.192.in-addr.arpa".to_string(), MDNSTYPE::PTR, true),
+            ]
+        );
+        let txt = responder
+            .records()
+            .find(|r| r.record.rr_type == MDNSTYPE::TXT)
+            .map(|r| r.record.rdata_as::<TXT>().unwrap());
+        assert_eq!(txt, Some(service.txt.clone()));
+
+        // Dropping the handle says goodbye, as soon as the idle responder is next driven.
+        while responder.poll_transmit().is_some() {}
+        assert_eq!(responder.poll_timeout(), None);
+        drop(handle);
+        let due = responder.poll_timeout().unwrap();
+        assert!(due <= last);
+        responder.handle_timeout(due);
+        let goodbye = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(goodbye.answers.len(), 7);
+        assert!(goodbye.answers.iter().all(|r| r.ttl == 0));
+        assert_eq!(responder.records().count(), 0);
+    }
+
+    #[test]
+    fn test_publish_host() {
+        let now = Instant::now();
+        let mut host = HostInfo {
+            name: MDNSFQDN::new("laptop.local"),
+            addresses: vec![Ipv4Addr::new(10, 0, 0, 5).into()],
+            hinfo: Some(HINFO::new("x86_64", "linux").unwrap()),
+        };
+
+        let mut responder = Responder::with_seed(0);
+        let handle = responder.publish_host(&host, now);
+        let (last, _) = settle(&mut responder, None).pop().unwrap();
+        assert_eq!(
+            responder.poll_event(),
+            Some(ResponderEvent::Registered(handle.id))
+        );
+
+        let names = responder
+            .records()
+            .map(|r| (r.record.rr_name.to_string(), r.record.rr_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("laptop.local".to_string(), MDNSTYPE::A),
+                ("laptop.local".to_string(), MDNSTYPE::HINFO),
+                ("5.0.0.10.in-addr.arpa".to_string(), MDNSTYPE::PTR),
+            ]
+        );
+
+        // Unchanged addresses are left alone.
+        while responder.poll_transmit().is_some() {}
+        responder.update_host(&handle, &host, last);
+        assert_eq!(responder.poll_timeout(), None);
+
+        // A new address says goodbye to the old one and announces the new one.
+        host.addresses = vec![Ipv4Addr::new(10, 0, 0, 6).into()];
+        let later = last + Duration::from_secs(5);
+        responder.update_host(&handle, &host, later);
+        let goodbye = unpack(&responder.poll_transmit().unwrap());
+        let gone = goodbye
+            .answers
+            .iter()
+            .map(|r| (r.rr_name.to_string(), r.ttl))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gone,
+            [
+                ("laptop.local".to_string(), 0),
+                ("5.0.0.10.in-addr.arpa".to_string(), 0),
+            ]
+        );
+
+        responder.handle_timeout(later);
+        let announcement = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(announcement.answers.len(), 3);
+        assert!(announcement
+            .answers
+            .iter()
+            .any(|r| r.rr_name.to_string() == "6.0.0.10.in-addr.arpa"));
+        assert!(announcement.answers.iter().all(|r| r.cache_flush()));
+    }
+
+    #[test]
+    fn test_refresh_host() {
+        let now = Instant::now();
+        let first = IpAddr::from(Ipv4Addr::new(10, 0, 0, 5));
+        let second = IpAddr::from(Ipv4Addr::new(10, 0, 0, 6));
+        let mut host = HostInfo {
+            name: MDNSFQDN::new("laptop.local"),
+            addresses: vec![first],
+            hinfo: None,
+        };
+
+        let mut responder = Responder::with_seed(0);
+        let handle = responder.publish_host(&host, now);
+        let (last, _) = settle(&mut responder, None).pop().unwrap();
+        let now = last + Duration::from_secs(5);
+
+        // The same addresses, in whatever order, change nothing.
+        assert!(!responder.update_host_addresses(&handle, &mut host, vec![first, first], now));
+        assert_eq!(responder.poll_timeout(), None);
+
+        // A new address is announced, along with the one still there.
+        assert!(responder.update_host_addresses(&handle, &mut host, vec![second, first], now));
+        assert!(responder.poll_transmit().is_none());
+        responder.handle_timeout(now);
+        let announcement = unpack(&responder.poll_transmit().unwrap());
+        let announced = announcement
+            .answers
+            .iter()
+            .map(|r| r.rr_name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            announced,
+            [
+                "laptop.local",
+                "laptop.local",
+                "5.0.0.10.in-addr.arpa",
+                "6.0.0.10.in-addr.arpa"
+            ]
+        );
+        settle(&mut responder, None);
+
+        // A removed one gets a goodbye before the rest are announced again.
+        let now = now + Duration::from_secs(10);
+        assert!(responder.update_host_addresses(&handle, &mut host, vec![second], now));
+        let goodbye = unpack(&responder.poll_transmit().unwrap());
+        let gone = goodbye
+            .answers
+            .iter()
+            .map(|r| (r.rr_name.to_string(), r.rdata().to_vec(), r.ttl))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gone,
+            [
+                ("laptop.local".to_string(), vec![10, 0, 0, 5], 0),
+                (
+                    "5.0.0.10.in-addr.arpa".to_string(),
+                    host.name.pack().into_vec(),
+                    0
+                ),
+            ]
+        );
+        responder.handle_timeout(now);
+        let announcement = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(announcement.answers.len(), 2);
+        assert!(announcement.answers.iter().all(|r| r.ttl > 0));
+        assert_eq!(host.addresses, [second]);
+
+        // Reading the machine's own addresses twice finds no change the second time.
+        let _ = responder.refresh_host(&handle, &mut host, now);
+        assert!(!responder.refresh_host(&handle, &mut host, now).unwrap());
+    }
+
+    #[test]
+    fn test_update_while_probing() {
+        let now = Instant::now();
+        let mut host = HostInfo {
+            name: MDNSFQDN::new("laptop.local"),
+            addresses: vec![Ipv4Addr::new(10, 0, 0, 5).into()],
+            hinfo: None,
+        };
+
+        let mut responder = Responder::with_seed(0);
+        let handle = responder.publish_host(&host, now);
+        let at = responder.poll_timeout().unwrap();
+        responder.handle_timeout(at);
+        assert!(!unpack(&responder.poll_transmit().unwrap())
+            .header
+            .is_response());
+
+        // Nothing was announced, so there are no goodbyes, but the new address is probed for in
+        // full.
+        host.addresses = vec![Ipv4Addr::new(10, 0, 0, 6).into()];
+        responder.update_host(&handle, &host, at);
+        assert!(responder.poll_transmit().is_none());
+
+        let (announcements, probes): (Vec<_>, Vec<_>) = settle(&mut responder, None)
+            .into_iter()
+            .map(|(_, transmit)| unpack(&transmit))
+            .partition(|packet| packet.header.is_response());
+        assert_eq!(probes.len(), PROBE_COUNT as usize);
+        assert!(probes
+            .iter()
+            .flat_map(|p| &p.authorities)
+            .filter(|r| r.rr_type == MDNSTYPE::A)
+            .all(|r| r.rdata() == [10, 0, 0, 6]));
+        assert_eq!(
+            responder.poll_event(),
+            Some(ResponderEvent::Registered(handle.id))
+        );
+        assert!(announcements
+            .iter()
+            .flat_map(|a| &a.answers)
+            .all(|r| r.ttl > 0 && r.rr_name.to_string() != "5.0.0.10.in-addr.arpa"));
+    }
+
+    #[test]
+    fn test_publish_host_hears_itself() {
+        let now = Instant::now();
+        let host = HostInfo {
+            name: MDNSFQDN::new("laptop.local"),
+            addresses: vec![
+                Ipv4Addr::new(10, 0, 0, 5).into(),
+                Ipv4Addr::new(192, 168, 1, 5).into(),
+                "fe80::5".parse::<Ipv6Addr>().unwrap().into(),
+                "fd00::5".parse::<Ipv6Addr>().unwrap().into(),
+            ],
+            hinfo: None,
+        };
+
+        // A host on several networks answers with several addresses of each family, all of which
+        // it hears back with multicast loopback on.
+        let mut responder = Responder::with_seed(0);
+        let handle = responder.publish_host(&host, now);
+        settle(&mut responder, Some("10.0.0.5:5353".parse().unwrap()));
+        let events = std::iter::from_fn(|| responder.poll_event()).collect::<Vec<_>>();
+        assert_eq!(events, [ResponderEvent::Registered(handle.id)]);
+        // Four address records and their reverse-mapping PTR records.
+        assert_eq!(responder.records().count(), 8);
+    }
+
+    #[test]
+    fn test_publish_domain() {
+        let now = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let domain = MDNSFQDN::new("example.com");
+
+        let mut responder = Responder::with_seed(0);
+        let published = responder.publish_domain(DomainKind::Browse, &domain, now);
+        settle(&mut responder, None);
+
+        let later = now + Duration::from_secs(10);
+        responder.handle_query(&query("b._dns-sd._udp.local", MDNSTYPE::PTR), src, later);
+        let at = responder.poll_timeout().unwrap();
+        responder.handle_timeout(at);
+        let response = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].rdata_as::<MDNSFQDN>().unwrap(), domain);
+
+        // Only for the kind it was given.
+        let later = later + Duration::from_secs(1);
+        responder.handle_query(&query("r._dns-sd._udp.local", MDNSTYPE::PTR), src, later);
+        assert_eq!(responder.poll_timeout(), None);
+
+        drop(published);
+        responder.handle_timeout(later);
+        let goodbye = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(goodbye.answers[0].ttl, 0);
+    }
+
+    #[test]
+    fn test_service_type_enumeration() {
+        let now = Instant::now();
+        let src = "192.168.1.10:5353".parse().unwrap();
+        let service = |instance: &str| ServiceInfo {
+            name: ServiceInstanceName::new(instance, "_http._tcp".parse().unwrap(), "local")
+                .unwrap(),
+            port: 80,
+            host: MDNSFQDN::new("printer.local"),
+            txt: TXT::default(),
+            subtypes: vec![],
+            addresses: vec![],
+        };
+
+        let mut responder = Responder::with_seed(0);
+        let first = responder.register_service(&service("First"), now);
+        let second = responder.register_service(&service("Second"), now);
+        settle(&mut responder, None);
+
+        // Both services share one answer to the meta-query.
+        let later = now + Duration::from_secs(10);
+        let meta = query("_services._dns-sd._udp.local", MDNSTYPE::PTR);
+        responder.handle_query(&meta, src, later);
+        responder.handle_timeout(responder.poll_timeout().unwrap());
+        let response = unpack(&responder.poll_transmit().unwrap());
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(
+            response.answers[0].rdata_as::<MDNSFQDN>().unwrap(),
+            MDNSFQDN::new("_http._tcp.local")
+        );
+
+        // Which only says goodbye once the last of them is gone.
+        let goodbye_types = |responder: &mut Responder| {
+            responder.handle_timeout(later);
+            let goodbye = unpack(&responder.poll_transmit().unwrap());
+            goodbye
+                .answers
+                .iter()
+                .map(|r| r.rr_name.to_string())
+                .collect::<Vec<_>>()
+        };
+        drop(first);
+        assert!(!goodbye_types(&mut responder).contains(&"_services._dns-sd._udp.local".into()));
+        drop(second);
+        assert!(goodbye_types(&mut responder).contains(&"_services._dns-sd._udp.local".into()));
+
+        // Nor while another registration is still probing for it.
+        let third = responder.register_service(&service("Third"), later);
+        settle(&mut responder, None);
+        let _fourth = responder.register_service(&service("Fourth"), later);
+        drop(third);
+        assert!(!goodbye_types(&mut responder).contains(&"_services._dns-sd._udp.local".into()));
+    }
+
+    #[test]
+    fn test_legacy_unicast() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(0);
+        responder.register(service_records(), now);
+        settle(&mut responder, None);
+
+        let src = "192.168.1.10:54321".parse().unwrap();
+        let mut legacy = query("_http._tcp.local", MDNSTYPE::PTR);
+        legacy.header.transaction_id = 0x1234;
+        responder.handle_query(&legacy, src, now);
+
+        // Answered straight away, even though the PTR record is shared.
+        let transmit = responder.poll_transmit().unwrap();
+        assert_eq!(transmit.destination, Destination::Unicast(src));
+
+        let response = unpack(&transmit);
+        assert_eq!(response.header.transaction_id, 0x1234);
+        assert_eq!(response.queries, legacy.queries);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.additional.len(), 3);
+        let records = response.answers.iter().chain(response.additional.iter());
+        assert!(records.clone().all(|r| r.ttl == LEGACY_MAX_TTL));
+        assert!(records.clone().all(|r| !r.cache_flush()));
+    }
+}
@@ -0,0 +1,183 @@
+use std::{cmp::Ordering, ops::RangeInclusive, time::Duration};
+
+use crate::packets::{
+    fqdn::MDNSFQDN, packet::MDNSPacket, query::MDNSQuery, resource_record::MDNSResourceRecord,
+    MDNSTYPE,
+};
+
+/* https://www.rfc-editor.org/rfc/rfc6762.html#section-8.1
+    When the host is ready to send its probe query, it SHOULD first wait for a short
+    random delay time, uniformly distributed in the range 0-250 ms. ... 250 ms after
+    the first query, the host should send a second; then, 250 ms after that, a third.
+    If, by 250 ms after the third probe, no conflicting Multicast DNS responses have
+    been received, the host may move to the next step, announcing.
+*/
+pub const INITIAL_DELAY_MS: RangeInclusive<u64> = 0..=250;
+pub const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+pub const PROBE_COUNT: u8 = 3;
+/// How long the loser of a simultaneous probe tiebreak waits before probing again.
+pub const TIEBREAK_DELAY: Duration = Duration::from_secs(1);
+
+/// Builds a probe asking `ANY` about every name in `proposed`, with the proposed records in the
+/// authority section. Only the first probe asks for unicast responses.
+pub fn probe_packet(proposed: &[MDNSResourceRecord], first: bool) -> MDNSPacket {
+    let mut names: Vec<&MDNSFQDN> = vec![];
+    for record in proposed {
+        if !names
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(&record.rr_name))
+        {
+            names.push(&record.rr_name);
+        }
+    }
+
+    let queries = names
+        .into_iter()
+        .map(|name| MDNSQuery::from_fqdn(name.clone(), MDNSTYPE::ANY, first))
+        .collect();
+
+    MDNSPacket::with_queries(queries).with_authorities(proposed.to_vec())
+}
+
+/// Whether `theirs` claims one of our proposed names, types and classes with rdata none of our
+/// records for it hold. A unique RRset may hold several records, such as a host's addresses.
+pub fn conflicts(proposed: &[MDNSResourceRecord], theirs: &MDNSResourceRecord) -> bool {
+    let same_set = proposed
+        .iter()
+        .filter(|ours| {
+            ours.rr_type == theirs.rr_type
+                && ours.class() == theirs.class()
+                && ours.rr_name.eq_ignore_ascii_case(&theirs.rr_name)
+        })
+        .collect::<Vec<_>>();
+
+    !same_set.is_empty() && same_set.iter().all(|ours| ours.rdata() != theirs.rdata())
+}
+
+/* https://www.rfc-editor.org/rfc/rfc6762.html#section-8.2
+    The bytes of the raw uncompressed rdata are compared in turn, interpreting the
+    bytes as eight-bit unsigned values, until a byte is found whose value is greater
+    than that of its counterpart (in which case, the rdata whose byte has the greater
+    value is deemed lexicographically later) or one of the resource records runs out
+    of rdata (in which case, the resource record which still has remaining data first
+    is deemed lexicographically later).
+*/
+/// Compares our proposed records named `name` against those in another host's simultaneous
+/// probe, by class, then type, then raw rdata. `Ordering::Less` means we lost.
+pub fn tiebreak(
+    name: &MDNSFQDN,
+    ours: &[MDNSResourceRecord],
+    theirs: &[MDNSResourceRecord],
+) -> Ordering {
+    let sorted = |records: &[MDNSResourceRecord]| {
+        let mut sorted = records
+            .iter()
+            .filter(|r| r.rr_name.eq_ignore_ascii_case(name))
+            .map(|r| (r.class(), r.rr_type as u16, r.rdata().to_vec()))
+            .collect::<Vec<_>>();
+        sorted.sort();
+        sorted
+    };
+
+    sorted(ours).cmp(&sorted(theirs))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::super::{
+        tests::{settle, unpack},
+        AuthoritativeRecord, Responder, ResponderEvent,
+    };
+    use super::*;
+
+    fn host_record(address: [u8; 4]) -> Vec<AuthoritativeRecord> {
+        vec![AuthoritativeRecord::unique(MDNSResourceRecord::new(
+            MDNSFQDN::new("printer.local"),
+            MDNSTYPE::A,
+            false,
+            120,
+            address.to_vec(),
+        ))]
+    }
+
+    /// Runs both responders on a shared virtual link, each hearing its own packets too, until
+    /// neither has anything left to do.
+    fn simulate(responders: &mut [Responder; 2], now: Instant) -> [Vec<ResponderEvent>; 2] {
+        let addresses = ["192.168.1.20:5353", "192.168.1.30:5353"].map(|a| a.parse().unwrap());
+        let mut events = [vec![], vec![]];
+
+        while let Some(at) = responders.iter().filter_map(|r| r.poll_timeout()).min() {
+            assert!(
+                at < now + Duration::from_secs(10),
+                "responders never settled"
+            );
+
+            for i in 0..2 {
+                responders[i].handle_timeout(at);
+                while let Some(transmit) = responders[i].poll_transmit() {
+                    let packet = unpack(&transmit);
+                    for receiver in responders.iter_mut() {
+                        match packet.header.is_response() {
+                            true => receiver.handle_response(&packet, at),
+                            false => receiver.handle_query(&packet, addresses[i], at),
+                        }
+                    }
+                }
+            }
+
+            for (i, responder) in responders.iter_mut().enumerate() {
+                events[i].extend(std::iter::from_fn(|| responder.poll_event()));
+            }
+        }
+
+        events
+    }
+
+    #[test]
+    fn test_simultaneous_probe_tiebreak() {
+        let now = Instant::now();
+        let mut responders = [Responder::with_seed(1), Responder::with_seed(2)];
+        let early = responders[0].register(host_record([192, 168, 1, 20]), now);
+        let late = responders[1].register(host_record([192, 168, 1, 30]), now);
+
+        // The lexicographically later address wins, and the loser backs off, is then defended
+        // against, and settles on another name.
+        let [lost, won] = simulate(&mut responders, now);
+        let renamed = ResponderEvent::Renamed {
+            id: early,
+            from: MDNSFQDN::new("printer.local"),
+            to: MDNSFQDN::new("printer-2.local"),
+        };
+        assert_eq!(lost, [renamed, ResponderEvent::Registered(early)]);
+        assert_eq!(won, [ResponderEvent::Registered(late)]);
+    }
+
+    #[test]
+    fn test_identical_probes_do_not_conflict() {
+        let now = Instant::now();
+        let mut responders = [Responder::with_seed(1), Responder::with_seed(2)];
+        let a = responders[0].register(host_record([192, 168, 1, 20]), now);
+        let b = responders[1].register(host_record([192, 168, 1, 20]), now);
+
+        let [events_a, events_b] = simulate(&mut responders, now);
+        assert_eq!(events_a, [ResponderEvent::Registered(a)]);
+        assert_eq!(events_b, [ResponderEvent::Registered(b)]);
+    }
+
+    #[test]
+    fn test_own_rrset_does_not_conflict() {
+        let now = Instant::now();
+        let mut responder = Responder::with_seed(1);
+        let mut records = host_record([192, 168, 1, 20]);
+        records.extend(host_record([10, 0, 0, 20]));
+        let id = responder.register(records, now);
+
+        // Hearing our own probes and announcements, with multicast loopback on, is harmless.
+        settle(&mut responder, Some("192.168.1.20:5353".parse().unwrap()));
+        let events = std::iter::from_fn(|| responder.poll_event()).collect::<Vec<_>>();
+        assert_eq!(events, [ResponderEvent::Registered(id)]);
+        assert_eq!(responder.records().count(), 4);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::packets::{
+    fqdn::{Label, MDNSFQDN},
+    pack::Packable,
+    rdata::SRV,
+    MDNSTYPE,
+};
+
+use super::AuthoritativeRecord;
+
+/// Picks the name to try after `name` conflicted.
+///
+/// Service instances count up as `Name (2)`, `Name (3)`, ... while host names count up as
+/// `host-2.local`, `host-3.local`, ...
+pub fn next_name(name: &MDNSFQDN) -> MDNSFQDN {
+    let mut labels = name.labels.clone();
+    let Some(Label::String(first)) = labels.first() else {
+        return name.clone();
+    };
+
+    let renamed = if is_service_instance(name) {
+        let (base, n) = first
+            .strip_suffix(')')
+            .and_then(|s| s.rsplit_once(" ("))
+            .and_then(|(base, n)| Some((base, n.parse::<u32>().ok()?)))
+            .unwrap_or((first, 1));
+        format!("{base} ({})", n + 1)
+    } else {
+        let (base, n) = first
+            .rsplit_once('-')
+            .and_then(|(base, n)| Some((base, n.parse::<u32>().ok()?)))
+            .unwrap_or((first, 1));
+        format!("{base}-{}", n + 1)
+    };
+
+    labels[0] = Label::String(renamed);
+    MDNSFQDN { labels }
+}
+
+/// Whether `name` looks like `<Instance>._service._tcp|_udp.<domain>`.
+fn is_service_instance(name: &MDNSFQDN) -> bool {
+    match &name.labels[..] {
+        [_, Label::String(service), Label::String(protocol), ..] => {
+            service.starts_with('_') && ["_tcp", "_udp"].contains(&protocol.as_str())
+        }
+        _ => false,
+    }
+}
+
+/// Renames every record named `old`, along with any `PTR` or `SRV` rdata pointing at it.
+pub fn rename_records(records: &mut [AuthoritativeRecord], old: &MDNSFQDN, new: &MDNSFQDN) {
+    for AuthoritativeRecord { record, .. } in records {
+        if record.rr_name.eq_ignore_ascii_case(old) {
+            record.rr_name = new.clone();
+        }
+
+        let rdata = match record.rr_type {
+            MDNSTYPE::PTR => record
+                .rdata_as::<MDNSFQDN>()
+                .ok()
+                .filter(|target| target.eq_ignore_ascii_case(old))
+                .map(|_| new.pack()),
+            MDNSTYPE::SRV => record
+                .rdata_as::<SRV>()
+                .ok()
+                .filter(|srv| srv.target.eq_ignore_ascii_case(old))
+                .map(|srv| {
+                    SRV {
+                        target: new.clone(),
+                        ..srv
+                    }
+                    .pack()
+                }),
+            _ => None,
+        };
+
+        if let Some(rdata) = rdata {
+            record.r_data = (rdata.into_vec(), None);
+            record.rd_length = record.r_data.0.len() as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_name() {
+        let renames = [
+            ("Printer._http._tcp.local", "Printer (2)._http._tcp.local"),
+            (
+                "Printer (2)._http._tcp.local",
+                "Printer (3)._http._tcp.local",
+            ),
+            (
+                "Office (Upstairs)._ipp._tcp.local",
+                "Office (Upstairs) (2)._ipp._tcp.local",
+            ),
+            ("printer.local", "printer-2.local"),
+            ("printer-9.local", "printer-10.local"),
+            ("my-printer.local", "my-printer-2.local"),
+        ];
+
+        for (name, renamed) in renames {
+            assert_eq!(next_name(&MDNSFQDN::new(name)), MDNSFQDN::new(renamed));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket};
+
+use anyhow::Result;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::transmit::{Destination, Transmit, MDNS_PORT};
+
+pub const MDNS_MULTICAST_IPV4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_MULTICAST_IPV6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x00fb);
+
+/// Binds a socket to port 5353 on the interface `(index, address)`, shared with any other mDNS
+/// software on the host, and joins the multicast group on it.
+///
+/// Responders send their unicast replies to QU questions to port 5353, see RFC 6762 section 5.4,
+/// so these arrive on the same socket as the multicast traffic.
+pub fn mdns_socket(interface: (u32, IpAddr)) -> Result<UdpSocket> {
+    let (index, address) = interface;
+    let domain = match address {
+        IpAddr::V4(_) => Domain::IPV4,
+        IpAddr::V6(_) => Domain::IPV6,
+    };
+
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+
+    match address {
+        IpAddr::V4(v4) => {
+            socket.bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), MDNS_PORT).into())?;
+            socket.join_multicast_v4(&MDNS_MULTICAST_IPV4, &v4)?;
+            socket.set_multicast_if_v4(&v4)?;
+            socket.set_multicast_loop_v4(true)?;
+            socket.set_multicast_ttl_v4(255)?;
+        }
+        IpAddr::V6(_) => {
+            socket.set_only_v6(true)?;
+            socket.bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), MDNS_PORT).into())?;
+            socket.join_multicast_v6(&MDNS_MULTICAST_IPV6, index)?;
+            socket.set_multicast_if_v6(index)?;
+            socket.set_multicast_loop_v6(true)?;
+            socket.set_multicast_hops_v6(255)?;
+        }
+    }
+
+    Ok(socket.into())
+}
+
+/// Sends a transmit from a socket created with [`mdns_socket`] on interface `index`.
+pub fn send(socket: &UdpSocket, index: u32, transmit: &Transmit) -> Result<()> {
+    let target = match (transmit.destination.clone(), socket.local_addr()?) {
+        (Destination::Unicast(addr), _) => addr,
+        (Destination::Multicast, SocketAddr::V4(_)) => {
+            SocketAddr::new(MDNS_MULTICAST_IPV4.into(), MDNS_PORT)
+        }
+        (Destination::Multicast, SocketAddr::V6(_)) => {
+            SocketAddrV6::new(MDNS_MULTICAST_IPV6, MDNS_PORT, 0, index).into()
+        }
+    };
+
+    socket.send_to(&transmit.packet, target)?;
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+
+pub const MDNS_PORT: u16 = 5353;
+
+/// The largest packet we send, an Ethernet MTU minus the IPv6 and UDP headers.
+pub const MAX_PACKET_SIZE: usize = 1452;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Destination {
+    /// The mDNS multicast group of whichever interface the packet is sent on.
+    Multicast,
+    Unicast(SocketAddr),
+}
+
+/// A packed packet waiting to be sent by whoever drives the querier or responder.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Transmit {
+    pub destination: Destination,
+    pub packet: Vec<u8>,
+}
+
+impl Transmit {
+    pub fn multicast(packet: crate::Data) -> Self {
+        Transmit {
+            destination: Destination::Multicast,
+            packet: packet.into_vec(),
+        }
+    }
+}